@@ -0,0 +1,45 @@
+//! The clock used to timestamp messages.
+
+use chrono::{DateTime, Duration, Utc};
+
+/// A source of the current time for message timestamps, e.g. [`Version::timestamp`].
+///
+/// [`Version::timestamp`]: crate::protocol::payload::Version::timestamp
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Clock {
+    /// The system clock.
+    #[default]
+    Real,
+    /// Always the same time, for deterministic messages.
+    Fixed(DateTime<Utc>),
+    /// The system clock shifted by the offset (which may be negative), e.g. to present a peer
+    /// whose clock is off.
+    Offset(Duration),
+}
+
+impl Clock {
+    /// Returns the current time according to the clock.
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Self::Real => Utc::now(),
+            Self::Fixed(time) => *time,
+            Self::Offset(offset) => Utc::now() + *offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn fixed_and_offset_clocks() {
+        let time = DateTime::<Utc>::from_timestamp(1_600_000_000, 0).unwrap();
+        assert_eq!(Clock::Fixed(time).now(), time);
+
+        let offset = Duration::hours(-3);
+        let skew = Clock::Offset(offset).now() - Clock::Real.now();
+        assert!((skew - offset).num_seconds().abs() <= 1);
+    }
+}
@@ -1,10 +1,6 @@
 use crate::protocol::{
-    message::{constants::*, Message, MessageHeader},
-    payload::{
-        block::{Block, Headers, LocatorHashes},
-        codec::Codec,
-        Addr, Inv, Nonce, Reject, Tx, Version,
-    },
+    message::{constants::MAX_MESSAGE_LEN, Message, MessageHeader},
+    payload::codec::Codec,
 };
 
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
@@ -58,33 +54,19 @@ impl Message {
     pub async fn read_from_stream<T: AsyncReadExt + Unpin>(stream: &mut T) -> io::Result<Self> {
         let header = MessageHeader::read_from_stream(stream).await?;
 
-        let mut buffer = vec![0u8; header.body_length as usize];
-        stream
-            .read_exact(&mut buffer[..header.body_length as usize])
-            .await?;
-
-        let mut bytes = Cursor::new(&buffer[..]);
+        if header.body_length as usize > MAX_MESSAGE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Message length of {} exceeds max message length of {}",
+                    header.body_length, MAX_MESSAGE_LEN
+                ),
+            ));
+        }
 
-        let message = match header.command {
-            VERSION_COMMAND => Self::Version(Version::decode(&mut bytes)?),
-            VERACK_COMMAND => Self::Verack,
-            PING_COMMAND => Self::Ping(Nonce::decode(&mut bytes)?),
-            PONG_COMMAND => Self::Pong(Nonce::decode(&mut bytes)?),
-            GETADDR_COMMAND => Self::GetAddr,
-            ADDR_COMMAND => Self::Addr(Addr::decode(&mut bytes)?),
-            GETHEADERS_COMMAND => Self::GetHeaders(LocatorHashes::decode(&mut bytes)?),
-            HEADERS_COMMAND => Self::Headers(Headers::decode(&mut bytes)?),
-            GETBLOCKS_COMMAND => Self::GetBlocks(LocatorHashes::decode(&mut bytes)?),
-            BLOCK_COMMAND => Self::Block(Box::new(Block::decode(&mut bytes)?)),
-            GETDATA_COMMAND => Self::GetData(Inv::decode(&mut bytes)?),
-            INV_COMMAND => Self::Inv(Inv::decode(&mut bytes)?),
-            NOTFOUND_COMMAND => Self::NotFound(Inv::decode(&mut bytes)?),
-            MEMPOOL_COMMAND => Self::MemPool,
-            TX_COMMAND => Self::Tx(Tx::decode(&mut bytes)?),
-            REJECT_COMMAND => Self::Reject(Reject::decode(&mut bytes)?),
-            _ => unimplemented!(),
-        };
+        let mut buffer = vec![0u8; header.body_length as usize];
+        stream.read_exact(&mut buffer).await?;
 
-        Ok(message)
+        Self::decode(header.command, &mut Cursor::new(&buffer[..]))
     }
 }
@@ -1,4 +1,5 @@
 //! An implementation of the Zcash network protocol types and messages.
 
+pub mod clock;
 pub mod message;
 pub mod payload;
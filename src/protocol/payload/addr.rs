@@ -1,6 +1,9 @@
 //! Network address types.
 
-use crate::protocol::payload::{codec::Codec, read_n_bytes, read_timestamp};
+use crate::protocol::{
+    clock::Clock,
+    payload::{codec::Codec, read_n_bytes, read_timestamp},
+};
 
 use std::convert::TryInto;
 
@@ -29,7 +32,7 @@ impl Addr {
     }
 
     /// Returns an iterator over the list of network addresses.
-    pub fn iter(&self) -> std::slice::Iter<NetworkAddr> {
+    pub fn iter(&self) -> std::slice::Iter<'_, NetworkAddr> {
         self.addrs.iter()
     }
 }
@@ -60,8 +63,14 @@ impl NetworkAddr {
     /// Creates a new NetworkAddr with the given socket address, `last_seen=chrono::Utc::now()`,
     /// and `services=1` (only `NODE_NETWORK` is enabled).
     pub fn new(addr: SocketAddr) -> Self {
+        Self::new_with_clock(addr, Clock::Real)
+    }
+
+    /// Creates a new NetworkAddr like [`NetworkAddr::new`], with `last_seen` set by the given
+    /// clock.
+    pub fn new_with_clock(addr: SocketAddr, clock: Clock) -> Self {
         Self {
-            last_seen: Some(chrono::Utc::now()),
+            last_seen: Some(clock.now()),
             services: 1,
             addr,
         }
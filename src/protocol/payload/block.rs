@@ -6,10 +6,7 @@ use crate::protocol::payload::{
     read_n_bytes, Hash, ProtocolVersion, Tx, VarInt,
 };
 
-use std::{
-    convert::TryInto,
-    io::{self, Cursor, Write},
-};
+use std::io::{self, Cursor, Write};
 
 use sha2::Digest;
 
@@ -274,7 +271,7 @@ impl Header {
         let hash_bytes_1 = sha2::Sha256::digest(&buffer);
         let hash_bytes_2 = sha2::Sha256::digest(&hash_bytes_1);
 
-        let hash = Hash::new(hash_bytes_2.try_into().unwrap());
+        let hash = Hash::new(hash_bytes_2.into());
 
         Ok(hash)
     }
@@ -331,6 +328,8 @@ impl Header {
 mod tests {
     use hex::FromHex;
 
+    use std::convert::TryInto;
+
     use super::*;
     use crate::vectors::*;
 
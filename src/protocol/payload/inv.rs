@@ -48,6 +48,16 @@ impl InvHash {
     pub fn new(kind: ObjectKind, hash: Hash) -> Self {
         Self { kind, hash }
     }
+
+    /// Returns the object kind of this inventory.
+    pub fn kind(&self) -> ObjectKind {
+        self.kind
+    }
+
+    /// Returns the hash of the object.
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
 }
 
 impl Codec for InvHash {
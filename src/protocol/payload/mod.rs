@@ -1,6 +1,6 @@
 //! Network message payload types.
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
 use rand::{thread_rng, Rng};
 
 use std::io::{self, Cursor, Read, Write};
@@ -56,10 +56,15 @@ impl Codec for Nonce {
 }
 
 /// Specifies the protocol version.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
 pub struct ProtocolVersion(u32);
 
 impl ProtocolVersion {
+    /// Creates a `ProtocolVersion` instance.
+    pub fn new(version: u32) -> Self {
+        Self(version)
+    }
+
     /// The current protocol version.
     pub fn current() -> Self {
         Self(170_013)
@@ -182,7 +187,7 @@ impl VarStr {
 }
 
 /// A general purpose hash of length `32`.
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub struct Hash([u8; 32]);
 
 impl Hash {
@@ -223,7 +228,6 @@ pub fn read_n_bytes<const N: usize>(bytes: &mut Cursor<&[u8]>) -> io::Result<[u8
 /// Reads a timestamp from the bytes.
 pub fn read_timestamp(bytes: &mut Cursor<&[u8]>) -> io::Result<DateTime<Utc>> {
     let timestamp_i64 = i64::from_le_bytes(read_n_bytes(bytes)?);
-    let timestamp = NaiveDateTime::from_timestamp_opt(timestamp_i64, 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Bad UTC timestamp"))?;
-    Ok(DateTime::<Utc>::from_utc(timestamp, Utc))
+    DateTime::<Utc>::from_timestamp(timestamp_i64, 0)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Bad UTC timestamp"))
 }
@@ -4,10 +4,7 @@ use sha2::Digest;
 
 use crate::protocol::payload::{codec::Codec, read_n_bytes, Hash, VarInt};
 
-use std::{
-    convert::TryInto,
-    io::{self, Cursor, Read, Write},
-};
+use std::io::{self, Cursor, Read, Write};
 
 use crate::protocol::payload::inv::{InvHash, ObjectKind};
 
@@ -34,7 +31,7 @@ impl Tx {
         let hash_bytes_1 = sha2::Sha256::digest(&buffer);
         let hash_bytes_2 = sha2::Sha256::digest(&hash_bytes_1);
 
-        let hash = Hash::new(hash_bytes_2.try_into().unwrap());
+        let hash = Hash::new(hash_bytes_2.into());
 
         Ok(hash)
     }
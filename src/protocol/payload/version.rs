@@ -1,7 +1,11 @@
 //! Version payload types.
 
-use crate::protocol::payload::{
-    addr::NetworkAddr, codec::Codec, read_n_bytes, read_timestamp, Nonce, ProtocolVersion, VarStr,
+use crate::protocol::{
+    clock::Clock,
+    payload::{
+        addr::NetworkAddr, codec::Codec, read_n_bytes, read_timestamp, Nonce, ProtocolVersion,
+        VarStr,
+    },
 };
 
 use chrono::{DateTime, Utc};
@@ -38,10 +42,15 @@ impl Version {
     /// Constructs a `Version`, where `addr_recv` is the remote `zcashd`/`zebra` node address and
     /// `addr_from` is our local node address.
     pub fn new(addr_recv: SocketAddr, addr_from: SocketAddr) -> Self {
+        Self::new_with_clock(addr_recv, addr_from, Clock::Real)
+    }
+
+    /// Constructs a `Version` like [`Version::new`], timestamped by the given clock.
+    pub fn new_with_clock(addr_recv: SocketAddr, addr_from: SocketAddr, clock: Clock) -> Self {
         Self {
             version: ProtocolVersion::current(),
             services: 1,
-            timestamp: Utc::now(),
+            timestamp: clock.now(),
             addr_recv: NetworkAddr {
                 last_seen: None,
                 services: 1,
@@ -0,0 +1,253 @@
+//! Clusters of nodes connected to each other, for behaviours which only show up between real
+//! nodes (e.g. block propagation or addr gossip).
+
+use crate::{
+    setup::{node::Node, sockets::established_connections},
+    tools::synthetic_node::{SyntheticNode, SyntheticNodeBuilder},
+};
+
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Default time allowed for the nodes of a [`Cluster`] to connect to each other.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Describes how the nodes of a [`Cluster`] are connected, as edges from the node initiating
+/// the connection (via its initial peers) to its target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Topology {
+    /// Each node connects to the next one.
+    Line,
+    /// Each node connects to the next one, and the last node to the first one.
+    Ring,
+    /// Every other node connects to the hub (given by its index).
+    Star { hub: usize },
+    /// The given `(initiator, target)` edges, by node index.
+    Mesh(Vec<(usize, usize)>),
+}
+
+impl Topology {
+    /// Returns the `(initiator, target)` edges of the topology for `n` nodes.
+    pub fn edges(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut edges: Vec<_> = match self {
+            Self::Line => (1..n).map(|i| (i - 1, i)).collect(),
+            Self::Ring => (0..n).map(|i| (i, (i + 1) % n)).collect(),
+            Self::Star { hub } => (0..n).map(|i| (i, *hub)).collect(),
+            Self::Mesh(edges) => edges.clone(),
+        };
+
+        // Drop the self-loops, e.g. the hub of a star or a ring of a single node.
+        edges.retain(|(initiator, target)| initiator != target);
+        // A ring of two nodes would have the same connection twice.
+        edges.dedup_by(|a, b| (a.0, a.1) == (b.1, b.0));
+
+        edges
+    }
+}
+
+/// A builder for [`Cluster`].
+pub struct ClusterBuilder {
+    nodes: Vec<Node>,
+    topology: Topology,
+    connect_timeout: Duration,
+}
+
+impl ClusterBuilder {
+    /// Creates a builder for a [`Cluster`] of the given nodes (which may be of different kinds),
+    /// connected according to the topology.
+    ///
+    /// The nodes' initial peers are set by the cluster on start, any other configuration (e.g.
+    /// [`Node::max_peers`]) is kept.
+    pub fn new(nodes: Vec<Node>, topology: Topology) -> Self {
+        Self {
+            nodes,
+            topology,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+
+    /// Creates a builder for a [`Cluster`] of `n` nodes created with [`Node::new`].
+    pub fn with_node_count(n: usize, topology: Topology) -> io::Result<Self> {
+        let nodes = (0..n).map(|_| Node::new()).collect::<io::Result<_>>()?;
+
+        Ok(Self::new(nodes, topology))
+    }
+
+    /// Sets the time allowed for the nodes to connect to each other, defaults to 30 seconds.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Starts the nodes and waits until each edge of the topology is connected.
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] if they aren't connected in time, the nodes are
+    /// stopped in that case.
+    pub async fn start(self) -> io::Result<Cluster> {
+        let edges = self.topology.edges(self.nodes.len());
+        if let Some(edge) = edges
+            .iter()
+            .find(|(initiator, target)| *initiator.max(target) >= self.nodes.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("edge {:?} is out of the cluster's bounds", edge),
+            ));
+        }
+
+        let mut cluster = Cluster {
+            nodes: self.nodes,
+            edges,
+            synthetic_nodes: Vec::new(),
+        };
+
+        let mut peers: HashMap<usize, Vec<SocketAddr>> = HashMap::new();
+        for (initiator, target) in &cluster.edges {
+            peers
+                .entry(*initiator)
+                .or_default()
+                .push(cluster.nodes[*target].addr());
+        }
+
+        for (i, node) in cluster.nodes.iter_mut().enumerate() {
+            node.initial_peers(peers.remove(&i).unwrap_or_default());
+            node.start().await?;
+        }
+
+        let start = Instant::now();
+        while !cluster.is_connected()? {
+            if start.elapsed() > self.connect_timeout {
+                // The nodes are stopped on drop.
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the cluster's nodes didn't connect in time",
+                ));
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(cluster)
+    }
+}
+
+/// A set of running [`Node`]s connected to each other, see [`ClusterBuilder`].
+///
+/// [`SyntheticNode`]s can be attached to any of the nodes. Everything is torn down together with
+/// [`Cluster::stop`], or when the cluster is dropped.
+pub struct Cluster {
+    nodes: Vec<Node>,
+    edges: Vec<(usize, usize)>,
+    synthetic_nodes: Vec<SyntheticNode>,
+}
+
+impl Cluster {
+    /// Returns the nodes, indexed as in the topology.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns the node at `index`.
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    /// Returns the `(initiator, target)` edges between the nodes.
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+
+    /// Returns `true` if each edge has an established connection.
+    ///
+    /// The connections are looked up in `/proc` from the initiators' side, including their child
+    /// processes (e.g. when the node is started with `cargo run`).
+    pub fn is_connected(&self) -> io::Result<bool> {
+        let mut connections = HashMap::new();
+
+        for (initiator, target) in &self.edges {
+            if !connections.contains_key(initiator) {
+                let pid = self.nodes[*initiator].pid().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotConnected, "node isn't running")
+                })?;
+                connections.insert(*initiator, established_connections(pid)?);
+            }
+
+            if !connections[initiator].contains(&self.nodes[*target].addr()) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Builds a [`SyntheticNode`] and connects it to the node at `index` (the handshake being
+    /// the one configured by the builder), it's shut down along with the cluster.
+    pub async fn attach(
+        &mut self,
+        index: usize,
+        builder: &SyntheticNodeBuilder,
+    ) -> io::Result<&mut SyntheticNode> {
+        let synthetic_node = builder.build().await?;
+        synthetic_node.connect(self.nodes[index].addr()).await?;
+        self.synthetic_nodes.push(synthetic_node);
+
+        Ok(self.synthetic_nodes.last_mut().unwrap())
+    }
+
+    /// Returns the attached synthetic nodes, in order of attachment.
+    pub fn synthetic_nodes(&mut self) -> &mut [SyntheticNode] {
+        &mut self.synthetic_nodes
+    }
+
+    /// Shuts down the synthetic nodes and stops every node, returns the first error encountered
+    /// (e.g. a node which crashed).
+    ///
+    /// The nodes are stopped concurrently, so this takes about as long as the slowest node.
+    pub async fn stop(&mut self) -> io::Result<()> {
+        for synthetic_node in self.synthetic_nodes.drain(..) {
+            synthetic_node.shut_down();
+        }
+
+        let stopping: Vec<_> = self
+            .nodes
+            .drain(..)
+            .map(|mut node| {
+                tokio::spawn(async move {
+                    let stopped = node.stop().await;
+                    (node, stopped)
+                })
+            })
+            .collect();
+
+        // Every node is stopped, even if stopping an earlier one failed.
+        let mut result = Ok(());
+        for task in stopping {
+            let (node, stopped) = task.await.map_err(io::Error::other)?;
+            if result.is_ok() {
+                result = stopped.map(|_| ());
+            }
+            self.nodes.push(node);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn topology_edges() {
+        assert_eq!(Topology::Line.edges(3), vec![(0, 1), (1, 2)]);
+        assert_eq!(Topology::Ring.edges(3), vec![(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(Topology::Ring.edges(2), vec![(0, 1)]);
+        assert_eq!(Topology::Star { hub: 1 }.edges(3), vec![(0, 1), (2, 1)]);
+        assert_eq!(Topology::Ring.edges(1), vec![]);
+    }
+}
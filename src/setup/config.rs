@@ -1,33 +1,146 @@
 use serde::{Deserialize, Serialize};
+use toml::value::{Table, Value};
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
+    env,
     ffi::OsString,
     fs, io,
     io::{Error, ErrorKind},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
     path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
 
-use crate::setup::node::Action;
+use crate::setup::node::{Action, Readiness};
 
 // The names of the files the node configurations will be written to.
 const ZEBRA_CONFIG: &str = "zebra.toml";
 const ZCASHD_CONFIG: &str = "zcash.conf";
-const ZCASHD_CACHE: &str = "testnet3";
 
-// Ziggurat's configuration directory and file. Caches are written to this directory.
+// Ziggurat's configuration directory and file.
 const CONFIG: &str = ".ziggurat";
 const CONFIG_FILE: &str = "config.toml";
+// The environment variables overriding the configuration file's path and selecting its profile.
+const CONFIG_ENV: &str = "ZIGGURAT_CONFIG";
+const PROFILE_ENV: &str = "ZIGGURAT_PROFILE";
 
-const DEFAULT_PORT: u16 = 8080;
+// The prefix of the nodes' data directories, created in the system's temporary directory.
+const DATA_DIR_PREFIX: &str = "ziggurat";
 
-/// Convenience struct for reading Ziggurat's configuration file.
+// The default time allowed for a node to become ready.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+// The default time allowed for a node to shut down once terminated.
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+// The credentials of the nodes' RPC servers (where supported).
+pub(super) const RPC_USER: &str = "ziggurat";
+pub(super) const RPC_PASSWORD: &str = "ziggurat";
+
+/// Distinguishes the data directories of the nodes created by this process.
+static NEXT_DATA_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A node configuration of Ziggurat's configuration file, either at its top level or named in
+/// its `profiles` table.
 #[derive(Deserialize)]
-struct ConfigFile {
+struct Profile {
     kind: NodeKind,
     path: PathBuf,
     start_command: String,
+    /// Lines appended to the generated `zcash.conf` (zcashd only).
+    #[serde(default)]
+    extra_config_lines: Vec<String>,
+    /// A table merged into the generated `zebra.toml` (zebra only).
+    #[serde(default)]
+    config_overrides: Table,
+    /// The template the configuration file is rendered from (custom nodes only).
+    config_template: Option<PathBuf>,
+}
+
+/// Convenience struct for reading a configuration file with named profiles.
+#[derive(Deserialize)]
+struct ProfilesFile {
+    /// The profile used unless another one is selected.
+    default_profile: Option<String>,
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profile {
+    /// Reads the profile from Ziggurat's configuration file, see [`select_profile`].
+    fn read(name: Option<&str>) -> io::Result<(Option<String>, Self)> {
+        let path = config_path()?;
+        let contents = fs::read_to_string(&path).map_err(|e| {
+            Error::new(e.kind(), format!("couldn't read {}: {}", path.display(), e))
+        })?;
+        let requested = name
+            .map(str::to_owned)
+            .or_else(|| env::var(PROFILE_ENV).ok().filter(|name| !name.is_empty()));
+
+        let (name, mut profile) = select_profile(toml::from_str(&contents)?, requested)?;
+
+        // A relative template path is relative to the configuration file.
+        if let (Some(template), Some(dir)) = (&mut profile.config_template, path.parent()) {
+            *template = dir.join(&*template);
+        }
+
+        Ok((name, profile))
+    }
+}
+
+/// Picks the `requested` profile from the configuration file's contents, or else its default
+/// profile (or its only one). Returns the profile's name along with it, `None` for a
+/// configuration without named profiles.
+fn select_profile(
+    contents: Value,
+    requested: Option<String>,
+) -> io::Result<(Option<String>, Profile)> {
+    let has_profiles = contents
+        .as_table()
+        .is_some_and(|table| table.contains_key("profiles"));
+    if !has_profiles {
+        return match requested {
+            Some(name) => Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "profile {:?} requested, but the configuration has no profiles",
+                    name
+                ),
+            )),
+            None => Ok((None, contents.try_into()?)),
+        };
+    }
+
+    let mut file: ProfilesFile = contents.try_into()?;
+    let available = file
+        .profiles
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let name = match requested.or(file.default_profile) {
+        Some(name) => name,
+        None if file.profiles.len() == 1 => file.profiles.keys().next().unwrap().clone(),
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "no profile selected (with {} or `default_profile`), available: {}",
+                    PROFILE_ENV, available
+                ),
+            ))
+        }
+    };
+
+    match file.profiles.remove(&name) {
+        Some(profile) => Ok((Some(name), profile)),
+        None => Err(Error::new(
+            ErrorKind::NotFound,
+            format!("unknown profile {:?}, available: {}", name, available),
+        )),
+    }
 }
 
 /// Node configuration abstracted by a [`Node`] instance.
@@ -37,7 +150,8 @@ struct ConfigFile {
 ///
 /// [`Node`]: struct@crate::setup::node::Node
 pub(super) struct NodeConfig {
-    /// The path of the cache directory of the node; this is `~/.ziggurat`.
+    /// The data directory of the node, holding its configuration file, cache and logs. It's a
+    /// directory unique to the node in the system's temporary directory.
     pub(super) path: PathBuf,
     /// The socket address of the node.
     pub(super) local_addr: SocketAddr,
@@ -49,56 +163,93 @@ pub(super) struct NodeConfig {
     pub(super) log_to_stdout: bool,
     /// Defines the initial action to take once the node has started.
     pub(super) initial_action: Action,
+    /// Keep the data directory when the node is stopped.
+    pub(super) keep_data_dir: bool,
+    /// Defines when the node is considered started.
+    pub(super) readiness: Readiness,
+    /// The time allowed for the node to become ready.
+    pub(super) startup_timeout: Duration,
+    /// The time allowed for the node to shut down once terminated, before it's killed.
+    pub(super) shutdown_grace: Duration,
+    /// Fail to stop if the node doesn't shut down cleanly.
+    pub(super) require_clean_shutdown: bool,
+    /// Enable the node's RPC server.
+    pub(super) rpc: bool,
+    /// The address of the node's RPC server, assigned on start if enabled.
+    pub(super) rpc_addr: Option<SocketAddr>,
+    /// Extra lines for zcashd's configuration file, the later lines take precedence.
+    pub(super) extra_config_lines: Vec<String>,
+    /// Overrides merged into zebra's configuration file.
+    pub(super) config_overrides: Table,
 }
 
 impl NodeConfig {
     pub(super) fn new() -> io::Result<Self> {
-        // Set the port explicitly.
-        let mut local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
-        local_addr.set_port(DEFAULT_PORT);
+        let data_dir = format!(
+            "{}-{}-{}",
+            DATA_DIR_PREFIX,
+            process::id(),
+            NEXT_DATA_DIR.fetch_add(1, Ordering::Relaxed)
+        );
 
         Ok(Self {
-            path: home::home_dir()
-                .ok_or_else(|| Error::new(ErrorKind::NotFound, "couldn't find home directory"))?
-                .join(CONFIG),
-            local_addr,
+            path: std::env::temp_dir().join(data_dir),
+            local_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), free_port()?),
             initial_peers: HashSet::new(),
             max_peers: 50,
             log_to_stdout: false,
             initial_action: Action::None,
+            keep_data_dir: false,
+            readiness: Readiness::Listening,
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            require_clean_shutdown: false,
+            rpc: false,
+            rpc_addr: None,
+            extra_config_lines: Vec::new(),
+            config_overrides: Table::new(),
         })
     }
 }
 
-/// Describes the node kind, currently supports the two known variants.
+/// Returns a port which is currently free on localhost.
+///
+/// The port is released before being returned, so it could in theory be taken by someone else
+/// before the node binds to it.
+pub(super) fn free_port() -> io::Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+
+    Ok(listener.local_addr()?.port())
+}
+
+/// Returns the path of Ziggurat's configuration file, `~/.ziggurat/config.toml` unless it's
+/// overridden with `ZIGGURAT_CONFIG`.
+fn config_path() -> io::Result<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    home::home_dir()
+        .map(|home| home.join(CONFIG).join(CONFIG_FILE))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "couldn't find home directory"))
+}
+
+/// Describes the node kind, either one of the two known variants or a custom node configured from
+/// a template.
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all(deserialize = "lowercase"))]
 pub(super) enum NodeKind {
     Zebra,
     Zcashd,
-}
-
-impl NodeKind {
-    /// Path to the configuration file for this [NodeKind]
-    pub(super) fn config_filepath(&self, wrapping_dir: &Path) -> PathBuf {
-        match self {
-            NodeKind::Zebra => wrapping_dir.join(ZEBRA_CONFIG),
-            NodeKind::Zcashd => wrapping_dir.join(ZCASHD_CONFIG),
-        }
-    }
-
-    pub(super) fn cache_path(&self, wrapping_dir: &Path) -> Option<PathBuf> {
-        match self {
-            NodeKind::Zebra => None,
-            NodeKind::Zcashd => Some(wrapping_dir.join(ZCASHD_CACHE)),
-        }
-    }
+    Custom,
 }
 
 /// Node configuration read from the `config.toml` file.
 #[derive(Clone)]
 pub(super) struct NodeMetaData {
-    /// The node kind (one of `Zebra` or `Zcashd`).
+    /// The name of the profile, `None` for a configuration without named profiles.
+    pub(super) profile: Option<String>,
+    /// The node kind (one of `Zebra`, `Zcashd` or `Custom`).
     pub(super) kind: NodeKind,
     /// The path to run the node's commands in.
     pub(super) path: PathBuf,
@@ -106,24 +257,78 @@ pub(super) struct NodeMetaData {
     pub(super) start_command: OsString,
     /// The args to run with the start command.
     pub(super) start_args: Vec<OsString>,
+    /// Lines appended to zcashd's configuration file.
+    pub(super) extra_config_lines: Vec<String>,
+    /// Overrides merged into zebra's configuration file.
+    pub(super) config_overrides: Table,
+    /// The template of a custom node's configuration file.
+    pub(super) config_template: Option<String>,
+    /// The path the node's configuration file is written to.
+    pub(super) config_file: PathBuf,
 }
 
 impl NodeMetaData {
-    pub(super) fn new(config_path: PathBuf) -> io::Result<Self> {
-        // Read Ziggurat's configuration file.
-        let path = config_path.join(CONFIG_FILE);
-        let config_string = fs::read_to_string(path)?;
-        let config_file: ConfigFile = toml::from_str(&config_string)?;
+    /// Reads the `profile` (or else the selected one) from Ziggurat's configuration file, the node
+    /// is run with `data_dir` as its data directory.
+    pub(super) fn new(profile: Option<&str>, data_dir: &Path) -> io::Result<Self> {
+        let (profile, config_file) = Profile::read(profile)?;
 
         let args_from = |command: &str| -> Vec<OsString> {
             command.split_whitespace().map(OsString::from).collect()
         };
 
+        // The extra configuration only applies to one kind of node, it's most likely a mistake
+        // if it's given for another one.
+        let misplaced = match config_file.kind {
+            NodeKind::Zebra | NodeKind::Custom if !config_file.extra_config_lines.is_empty() => {
+                Some("extra_config_lines")
+            }
+            NodeKind::Zcashd | NodeKind::Custom if !config_file.config_overrides.is_empty() => {
+                Some("config_overrides")
+            }
+            NodeKind::Zebra | NodeKind::Zcashd if config_file.config_template.is_some() => {
+                Some("config_template")
+            }
+            _ => None,
+        };
+        if let Some(field) = misplaced {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("`{}` isn't supported for {:?}", field, config_file.kind),
+            ));
+        }
+
+        // A custom node's configuration file is named after its template.
+        let (config_template, config_file_path) = match config_file.config_template {
+            Some(ref template) => {
+                let contents = fs::read_to_string(template).map_err(|e| {
+                    Error::new(
+                        e.kind(),
+                        format!("couldn't read {}: {}", template.display(), e),
+                    )
+                })?;
+                let name = template.file_name().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidData, "`config_template` isn't a file")
+                })?;
+
+                (Some(contents), data_dir.join(name))
+            }
+            None => match config_file.kind {
+                NodeKind::Zebra => (None, data_dir.join(ZEBRA_CONFIG)),
+                NodeKind::Zcashd => (None, data_dir.join(ZCASHD_CONFIG)),
+                NodeKind::Custom => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "custom nodes require a `config_template`",
+                    ))
+                }
+            },
+        };
+
         let mut start_args = args_from(&config_file.start_command);
         let start_command = start_args.remove(0);
 
         // Insert the node's config file path into start args.
-        let config_file_path = config_file.kind.config_filepath(&config_path);
         match config_file.kind {
             NodeKind::Zebra => {
                 // Zebra's final arg must be `start`, so we insert the actual args before it.
@@ -135,18 +340,33 @@ impl NodeMetaData {
                     ));
                 }
                 start_args.insert(n_args - 1, "--config".into());
-                start_args.insert(n_args, config_file_path.into_os_string());
+                start_args.insert(n_args, config_file_path.clone().into_os_string());
             }
             NodeKind::Zcashd => {
-                start_args.push(format!("-datadir={}", config_path.to_str().unwrap()).into());
+                start_args.push(format!("-datadir={}", data_dir.to_str().unwrap()).into());
+            }
+            NodeKind::Custom => {
+                // The args are given in full by the start command, with placeholders for the paths.
+                for arg in &mut start_args {
+                    *arg = arg
+                        .to_string_lossy()
+                        .replace("{config_file}", &config_file_path.to_string_lossy())
+                        .replace("{data_dir}", &data_dir.to_string_lossy())
+                        .into();
+                }
             }
         }
 
         Ok(Self {
+            profile,
             kind: config_file.kind,
             path: config_file.path,
             start_command,
             start_args,
+            extra_config_lines: config_file.extra_config_lines,
+            config_overrides: config_file.config_overrides,
+            config_template,
+            config_file: config_file_path,
         })
     }
 }
@@ -157,6 +377,8 @@ pub(super) struct ZebraConfigFile {
     network: NetworkConfig,
     state: StateConfig,
     tracing: TracingConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rpc: Option<RpcConfig>,
 }
 
 impl ZebraConfigFile {
@@ -184,10 +406,29 @@ impl ZebraConfigFile {
             tracing: TracingConfig {
                 filter: Some("zebra_network=trace,zebrad=trace".to_string()),
             },
+            rpc: config.rpc_addr.map(|listen_addr| RpcConfig { listen_addr }),
+        };
+
+        // Merge the overrides into the generated configuration and write the toml to a string.
+        let mut table = match Value::try_from(&zebra_config)? {
+            Value::Table(table) => table,
+            _ => unreachable!("the configuration is serialized as a table"),
         };
+        merge_tables(&mut table, &config.config_overrides);
 
-        // Write the toml to a string.
-        toml::to_string(&zebra_config)
+        toml::to_string(&table)
+    }
+}
+
+/// Merges the overrides into the table, recursing into the tables present in both.
+pub(super) fn merge_tables(table: &mut Table, overrides: &Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(key), value) {
+            (Some(Value::Table(table)), Value::Table(overrides)) => merge_tables(table, overrides),
+            _ => {
+                table.insert(key.clone(), value.clone());
+            }
+        }
     }
 }
 
@@ -210,15 +451,39 @@ struct TracingConfig {
     filter: Option<String>,
 }
 
+#[derive(Serialize)]
+struct RpcConfig {
+    listen_addr: SocketAddr,
+}
+
 /// Convenience struct for writing a zcashd compatible configuration file.
 pub(super) struct ZcashdConfigFile;
 
 impl ZcashdConfigFile {
     pub(super) fn generate(config: &NodeConfig) -> String {
-        let mut contents = format!(
+        // Zcashd keeps the first value of an option, so the extra lines come first (in reverse,
+        // the later ones taking precedence) to override the generated ones.
+        let mut contents = String::new();
+        for line in config.extra_config_lines.iter().rev() {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+
+        contents.push_str(&format!(
             "testnet=1\nwhitebind={}\nmaxconnections={}\n",
             config.local_addr, config.max_peers
-        );
+        ));
+
+        if let Some(rpc_addr) = config.rpc_addr {
+            contents.push_str(&format!(
+                "server=1\nrpcbind={}\nrpcport={}\nrpcallowip={}\nrpcuser={}\nrpcpassword={}\n",
+                rpc_addr.ip(),
+                rpc_addr.port(),
+                rpc_addr.ip(),
+                RPC_USER,
+                RPC_PASSWORD
+            ));
+        }
 
         if config.initial_peers.is_empty() {
             contents.push_str("addnode=\n")
@@ -231,3 +496,164 @@ impl ZcashdConfigFile {
         contents
     }
 }
+
+/// Renders the configuration file of a custom node from its template.
+///
+/// The template's placeholders are replaced with the node's configuration:
+///
+/// - `{listen_addr}`: the socket address the node listens on.
+/// - `{peers}`: the initial peers, comma separated.
+/// - `{peers_quoted}`: the initial peers, quoted and comma separated (e.g. for a toml array).
+/// - `{max_peers}`: the max number of peer connections.
+/// - `{network}`: the network, always `testnet`.
+/// - `{data_dir}`: the node's data directory.
+pub(super) struct CustomConfigFile;
+
+impl CustomConfigFile {
+    pub(super) fn generate(template: &str, config: &NodeConfig) -> String {
+        // Sorted so the rendering is deterministic.
+        let mut peers: Vec<&str> = config.initial_peers.iter().map(String::as_str).collect();
+        peers.sort_unstable();
+        let peers_quoted: Vec<String> = peers.iter().map(|peer| format!("\"{}\"", peer)).collect();
+
+        template
+            .replace("{listen_addr}", &config.local_addr.to_string())
+            .replace("{peers}", &peers.join(","))
+            .replace("{peers_quoted}", &peers_quoted.join(", "))
+            .replace("{max_peers}", &config.max_peers.to_string())
+            .replace("{network}", "testnet")
+            .replace("{data_dir}", &config.path.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn configs_get_distinct_ports_and_data_dirs() {
+        let a = NodeConfig::new().unwrap();
+        let b = NodeConfig::new().unwrap();
+
+        assert_ne!(a.local_addr, b.local_addr);
+        assert_ne!(a.path, b.path);
+        assert!(a.path.starts_with(std::env::temp_dir()));
+    }
+
+    #[test]
+    #[ignore]
+    fn rpc_is_only_configured_if_enabled() {
+        let mut config = NodeConfig::new().unwrap();
+        assert!(!ZcashdConfigFile::generate(&config).contains("server=1"));
+        assert!(!ZebraConfigFile::generate(&config)
+            .unwrap()
+            .contains("[rpc]"));
+
+        config.rpc_addr = Some("127.0.0.1:18232".parse().unwrap());
+        let zcashd = ZcashdConfigFile::generate(&config);
+        assert!(zcashd.contains("server=1\nrpcbind=127.0.0.1\nrpcport=18232\n"));
+        assert!(zcashd.contains(&format!("rpcuser={}\n", RPC_USER)));
+        let zebra = ZebraConfigFile::generate(&config).unwrap();
+        assert!(zebra.contains("[rpc]\nlisten_addr = \"127.0.0.1:18232\""));
+    }
+
+    #[test]
+    #[ignore]
+    fn extra_configuration_overrides_the_generated_one() {
+        let mut config = NodeConfig::new().unwrap();
+        config.extra_config_lines = vec!["maxconnections=1".into(), "maxconnections=2".into()];
+        let zcashd = ZcashdConfigFile::generate(&config);
+        assert!(zcashd.starts_with("maxconnections=2\nmaxconnections=1\ntestnet=1\n"));
+
+        config.config_overrides = toml::from_str(
+            "network.peerset_initial_target_size = 3\nmempool.eviction_memory_time = \"1s\"",
+        )
+        .unwrap();
+        let zebra: Table = toml::from_str(&ZebraConfigFile::generate(&config).unwrap()).unwrap();
+        assert_eq!(
+            zebra["network"]["peerset_initial_target_size"].as_integer(),
+            Some(3)
+        );
+        // The rest of the table is kept.
+        assert_eq!(zebra["network"]["network"].as_str(), Some("Testnet"));
+        assert_eq!(
+            zebra["mempool"]["eviction_memory_time"].as_str(),
+            Some("1s")
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn profiles_are_selected_by_name_or_default() {
+        let profile = |kind| {
+            format!(
+                "kind = \"{}\"\npath = \"/\"\nstart_command = \"{} start\"\n",
+                kind, kind
+            )
+        };
+        let select = |contents: &str, requested: Option<&str>| {
+            select_profile(
+                toml::from_str(contents).unwrap(),
+                requested.map(str::to_owned),
+            )
+            .map(|(name, profile)| (name, profile.kind))
+        };
+
+        // A configuration without named profiles.
+        let single = profile("zebra");
+        assert_eq!(select(&single, None).unwrap(), (None, NodeKind::Zebra));
+        assert_eq!(
+            select(&single, Some("zebra")).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        let profiles = format!(
+            "[profiles.zebra]\n{}[profiles.zcashd-4-4]\n{}",
+            profile("zebra"),
+            profile("zcashd")
+        );
+        assert_eq!(
+            select(&profiles, Some("zcashd-4-4")).unwrap(),
+            (Some("zcashd-4-4".to_owned()), NodeKind::Zcashd)
+        );
+        assert_eq!(
+            select(&profiles, None).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            select(&profiles, Some("zebra-fork")).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        let with_default = format!("default_profile = \"zebra\"\n{}", profiles);
+        assert_eq!(
+            select(&with_default, None).unwrap(),
+            (Some("zebra".to_owned()), NodeKind::Zebra)
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn custom_config_is_rendered_from_the_template() {
+        let mut config = NodeConfig::new().unwrap();
+        config.initial_peers = vec!["127.0.0.1:2".to_owned(), "127.0.0.1:1".to_owned()]
+            .into_iter()
+            .collect();
+        config.max_peers = 8;
+
+        let template = "listen = \"{listen_addr}\"\npeers = [{peers_quoted}]\n\
+                        seeds = {peers}\nmax = {max_peers}\nnet = {network}\ndir = {data_dir}\n\
+                        table = { unknown = 1 }";
+        assert_eq!(
+            CustomConfigFile::generate(template, &config),
+            format!(
+                "listen = \"{}\"\npeers = [\"127.0.0.1:1\", \"127.0.0.1:2\"]\n\
+                 seeds = 127.0.0.1:1,127.0.0.1:2\nmax = 8\nnet = testnet\ndir = {}\n\
+                 table = {{ unknown = 1 }}",
+                config.local_addr,
+                config.path.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Capture of a node's output (stdout and stderr).
+
+use parking_lot::Mutex;
+use regex::Regex;
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The number of lines kept in memory, the older ones are only kept in the log file.
+const LOG_CAPACITY: usize = 10_000;
+
+/// A position in a node's log, see [`Node::log_mark`].
+///
+/// [`Node::log_mark`]: crate::setup::node::Node::log_mark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogMark(u64);
+
+/// The most recent log lines, in a ring buffer.
+#[derive(Debug)]
+struct LogBuffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+    /// The number of lines evicted from the buffer.
+    evicted: u64,
+    file: Option<File>,
+}
+
+impl LogBuffer {
+    fn push(&mut self, line: String) {
+        if let Some(ref mut file) = self.file {
+            // A failed write shouldn't stop the capture, the lines are still in memory.
+            let _ = writeln!(file, "{}", line);
+        }
+
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.evicted += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    fn mark(&self) -> LogMark {
+        LogMark(self.evicted + self.lines.len() as u64)
+    }
+
+    /// Returns the lines since the mark, evicted lines are skipped.
+    fn since(&self, mark: LogMark) -> impl Iterator<Item = &String> {
+        let skip = mark.0.saturating_sub(self.evicted) as usize;
+        self.lines.iter().skip(skip)
+    }
+}
+
+/// The captured output of a node process, kept in memory and written to a log file.
+#[derive(Debug, Clone)]
+pub(super) struct NodeLog {
+    buffer: Arc<Mutex<LogBuffer>>,
+    path: Option<PathBuf>,
+    /// The number of outputs still being captured.
+    open: Arc<AtomicUsize>,
+}
+
+impl NodeLog {
+    /// Creates a log writing to the file at `path` (truncated), if any.
+    pub(super) fn new(path: Option<&Path>) -> io::Result<Self> {
+        Self::with_capacity(path, LOG_CAPACITY)
+    }
+
+    fn with_capacity(path: Option<&Path>, capacity: usize) -> io::Result<Self> {
+        let file = path.map(File::create).transpose()?;
+
+        Ok(Self {
+            buffer: Arc::new(Mutex::new(LogBuffer {
+                lines: VecDeque::with_capacity(capacity),
+                capacity,
+                evicted: 0,
+                file,
+            })),
+            path: path.map(Path::to_path_buf),
+            open: Default::default(),
+        })
+    }
+
+    /// Returns the path of the log file, if any.
+    pub(super) fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Captures the output line by line on a background thread, until it's closed. The lines
+    /// are also printed to stdout if `echo` is set.
+    pub(super) fn capture<R: Read + Send + 'static>(&self, output: R, echo: bool) {
+        let buffer = self.buffer.clone();
+        let open = self.open.clone();
+        open.fetch_add(1, Ordering::AcqRel);
+
+        thread::spawn(move || {
+            let mut output = BufReader::new(output);
+            let mut bytes = Vec::new();
+
+            // The output isn't necessarily valid UTF-8, it's decoded lossily.
+            while matches!(output.read_until(b'\n', &mut bytes), Ok(n) if n > 0) {
+                let line = String::from_utf8_lossy(&bytes)
+                    .trim_end_matches(&['\r', '\n'][..])
+                    .to_owned();
+                bytes.clear();
+
+                if echo {
+                    println!("{}", line);
+                }
+                buffer.lock().push(line);
+            }
+
+            open.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+
+    /// Waits until the captured outputs are closed (e.g. after the process exited), at most for
+    /// `timeout`.
+    ///
+    /// This blocks the thread, as it's meant for a process which already exited and is also used
+    /// from [`Drop`].
+    pub(super) fn wait_for_close(&self, timeout: Duration) {
+        let start = Instant::now();
+        while self.open.load(Ordering::Acquire) > 0 && start.elapsed() < timeout {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Waits until the captured outputs are closed, like [`NodeLog::wait_for_close`] but without
+    /// blocking the thread.
+    pub(super) async fn closed(&self, timeout: Duration) {
+        let start = Instant::now();
+        while self.open.load(Ordering::Acquire) > 0 && start.elapsed() < timeout {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// Returns the mark of the end of the log.
+    pub(super) fn mark(&self) -> LogMark {
+        self.buffer.lock().mark()
+    }
+
+    /// Returns the lines logged since the mark, which are still in memory.
+    pub(super) fn since(&self, mark: LogMark) -> Vec<String> {
+        self.buffer.lock().since(mark).cloned().collect()
+    }
+
+    /// Returns the last `n` lines.
+    pub(super) fn tail(&self, n: usize) -> Vec<String> {
+        let buffer = self.buffer.lock();
+        let skip = buffer.lines.len().saturating_sub(n);
+
+        buffer.lines.iter().skip(skip).cloned().collect()
+    }
+
+    /// Returns the first line matching the regex logged since the mark, along with the mark of
+    /// the end of the log (to continue the search from).
+    pub(super) fn find(&self, mark: LogMark, regex: &Regex) -> (Option<String>, LogMark) {
+        let buffer = self.buffer.lock();
+        let line = buffer
+            .since(mark)
+            .find(|line| regex.is_match(line))
+            .cloned();
+
+        (line, buffer.mark())
+    }
+
+    /// Waits for a line matching `pattern` logged since the mark, returns the line.
+    pub(super) async fn wait_for(
+        &self,
+        mark: LogMark,
+        pattern: &str,
+        timeout: Duration,
+    ) -> io::Result<String> {
+        let regex = compile(pattern)?;
+
+        let start = Instant::now();
+        let mut mark = mark;
+        loop {
+            match self.find(mark, &regex) {
+                (Some(line), _) => return Ok(line),
+                // Only search the new lines next time.
+                (None, end) => mark = end,
+            }
+
+            if start.elapsed() > timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no log line matched {:?} in {:?}", pattern, timeout),
+                ));
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+/// Compiles the regex, failing with [`io::ErrorKind::InvalidInput`].
+pub(super) fn compile(pattern: &str) -> io::Result<Regex> {
+    Regex::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[tokio::test]
+    #[ignore]
+    async fn captured_lines_are_searchable_since_a_mark() {
+        let log = NodeLog::with_capacity(None, 3).unwrap();
+        let start = log.mark();
+
+        log.capture(Cursor::new(b"one\ntwo\r\n".to_vec()), false);
+        assert_eq!(
+            log.wait_for(start, "^tw", Duration::from_secs(1))
+                .await
+                .unwrap(),
+            "two"
+        );
+
+        let mark = log.mark();
+        log.capture(Cursor::new(b"three\nfour".to_vec()), false);
+        log.wait_for(mark, "four", Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(log.since(mark), vec!["three", "four"]);
+        log.wait_for_close(Duration::from_secs(1));
+        assert_eq!(log.open.load(Ordering::Acquire), 0);
+        let (line, end) = log.find(start, &compile("^t").unwrap());
+        assert_eq!((line.as_deref(), end), (Some("two"), log.mark()));
+
+        // The first line was evicted.
+        assert_eq!(log.since(start), vec!["two", "three", "four"]);
+        assert_eq!(log.tail(1), vec!["four"]);
+
+        let error = log
+            .wait_for(mark, "one", Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -1,4 +1,7 @@
 //! Utilities for setting up and tearing down node instances (`zcashd` or `zebra`).
 
+pub mod cluster;
 mod config;
+pub mod logs;
 pub mod node;
+mod sockets;
@@ -3,13 +3,16 @@
 use tracing::error;
 
 use crate::{
-    protocol::payload::{
-        block::{Block, Headers},
-        Hash, Inv,
+    setup::{
+        config::{
+            free_port, merge_tables, CustomConfigFile, NodeConfig, NodeKind, NodeMetaData,
+            ZcashdConfigFile, ZebraConfigFile, RPC_PASSWORD, RPC_USER,
+        },
+        logs::{self, LogMark, NodeLog},
+        sockets,
     },
-    setup::config::{NodeConfig, NodeKind, NodeMetaData, ZcashdConfigFile, ZebraConfigFile},
     tools::{
-        message_filter::{Filter, MessageFilter},
+        chain::Chain, metrics::resources::ResourceSampler, rpc::RpcClient,
         synthetic_node::SyntheticNode,
     },
     wait_until,
@@ -17,7 +20,76 @@ use crate::{
 
 use std::process::{Child, Command};
 
-use std::{fs, io, net::SocketAddr, process::Stdio, time::Duration};
+use std::{
+    fmt, fs, io,
+    net::SocketAddr,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The number of log lines printed when a test fails.
+const FAILURE_LOG_TAIL: usize = 50;
+/// The number of log lines included in a startup error.
+const STARTUP_LOG_TAIL: usize = 20;
+/// The time allowed for the last lines of an exited node to be captured.
+const LOG_CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+/// The interval at which a terminated node is checked for exit.
+const TERMINATE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a node shut down when stopped, see [`Node::stop`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopReport {
+    /// The exit code, if the node exited by itself.
+    pub exit_code: Option<i32>,
+    /// The signal which terminated the node, if any.
+    pub signal: Option<i32>,
+    /// Whether the node had to be killed, as it didn't shut down within the grace period.
+    pub killed: bool,
+    /// The time in between terminating the node and its exit.
+    pub duration: Duration,
+}
+
+impl StopReport {
+    fn new(status: ExitStatus, killed: bool, duration: Duration) -> Self {
+        Self {
+            exit_code: status.code(),
+            signal: status.signal(),
+            killed,
+            duration,
+        }
+    }
+
+    /// Returns `true` if the node shut down by itself within the grace period, with a zero exit
+    /// code.
+    pub fn is_clean(&self) -> bool {
+        !self.killed && self.exit_code == Some(0)
+    }
+}
+
+impl fmt::Display for StopReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.killed, self.exit_code, self.signal) {
+            (true, _, _) => write!(f, "killed after the grace period")?,
+            (false, Some(code), _) => write!(f, "exited with code {}", code)?,
+            (false, None, Some(signal)) => write!(f, "terminated by signal {}", signal)?,
+            (false, None, None) => write!(f, "exited")?,
+        }
+
+        write!(f, " in {:?}", self.duration)
+    }
+}
+
+/// Describes when a started node is ready, see [`Node::readiness`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Readiness {
+    /// The node listens on its P2P port (checked via `/proc`, without connecting to it).
+    Listening,
+    /// The node logged a line matching the regex.
+    LogLine(String),
+}
 
 /// Actions to prepare node state on start.
 pub enum Action {
@@ -27,19 +99,23 @@ pub enum Action {
     /// This is useful for indicating that the node has started and is available for
     /// other connections.
     WaitForConnection,
-    /// Seeds the node with `n` blocks from the testnet chain, by connecting from a local socket
-    /// and sending the appropriate data. After this, the connection is terminated.
+    /// Seeds the node with `n` blocks from the testnet chain, by serving them as a [`Chain`] to the
+    /// node from a local listener. After this, the connection is terminated.
     ///
     /// **Warning**: this currently only works for zcashd type nodes, for zebra the behaviour
     /// is equivalent to WaitForConnection.
     SeedWithTestnetBlocks(
         /// The number of initial testnet blocks to seed. Note that this is capped by the number of blocks available
         /// from [Block::initial_testnet_blocks].
+        ///
+        /// [Block::initial_testnet_blocks]: crate::protocol::payload::block::Block::initial_testnet_blocks
         usize,
     ),
 }
 
 /// Represents an instance of a node, its configuration and setup/teardown intricacies.
+///
+/// A started node should be stopped with [`Node::stop`], dropping it kills the node instead.
 pub struct Node {
     /// Configuration definable in tests and written to the node's configuration file on start.
     config: NodeConfig,
@@ -48,10 +124,13 @@ pub struct Node {
     meta: NodeMetaData,
     /// Process of the running node.
     process: Option<Child>,
+    /// The captured output of the node, since it was last started.
+    log: Option<NodeLog>,
 }
 
 impl Node {
-    /// Creates a new [`Node`] instance.
+    /// Creates a new [`Node`] instance, from the profile of Ziggurat's configuration file selected
+    /// with the `ZIGGURAT_PROFILE` environment variable (or else its default profile).
     ///
     /// Once created, it can be configured with calls to [`initial_peers`], [`max_peers`] and [`log_to_stdout`].
     ///
@@ -61,22 +140,64 @@ impl Node {
     /// [`max_peers`]: method@Node::max_peers
     /// [`log_to_stdout`]: method@Node::log_to_stdout
     pub fn new() -> io::Result<Self> {
+        Self::from_profile(None)
+    }
+
+    /// Creates a new [`Node`] from the named profile of Ziggurat's configuration file, e.g. for
+    /// tests which need several kinds of node at once.
+    pub fn with_profile(profile: &str) -> io::Result<Self> {
+        Self::from_profile(Some(profile))
+    }
+
+    fn from_profile(profile: Option<&str>) -> io::Result<Self> {
         // Config (to be written to node configuration file).
-        let config = NodeConfig::new()?;
-        let meta = NodeMetaData::new(config.path.clone())?;
+        let mut config = NodeConfig::new()?;
+        let meta = NodeMetaData::new(profile, &config.path)?;
+
+        // The extra configuration from `config.toml`, the tests' own is applied on top.
+        config.extra_config_lines = meta.extra_config_lines.clone();
+        config.config_overrides = meta.config_overrides.clone();
 
         Ok(Self {
             config,
             meta,
             process: None,
+            log: None,
         })
     }
 
-    /// Returns the (external) address of the node.
+    /// Returns the name of the node's profile, `None` for a configuration without named profiles.
+    pub fn profile(&self) -> Option<&str> {
+        self.meta.profile.as_deref()
+    }
+
+    /// Returns the (external) address of the node, its port is picked from the free ports on
+    /// creation.
     pub fn addr(&self) -> SocketAddr {
         self.config.local_addr
     }
 
+    /// Returns the process id of the node, if it's running.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref().map(|process| process.id())
+    }
+
+    /// Starts sampling the resource usage of the running node every `interval`, see
+    /// [`ResourceSampler`].
+    pub fn resource_sampler(&self, interval: Duration) -> io::Result<ResourceSampler> {
+        let pid = self
+            .pid()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "node isn't running"))?;
+
+        Ok(ResourceSampler::start(pid, interval))
+    }
+
+    /// Returns the data directory of the node, which holds its configuration file, cache and
+    /// logs. It's unique to the node, so that several nodes can run at once.
+    pub fn data_dir(&self) -> &Path {
+        &self.config.path
+    }
+
     /// Sets the initial peers (ports only) for the node.
     ///
     /// The ip used to construct the addresses can be optionally set in the configuration file and
@@ -93,12 +214,92 @@ impl Node {
         self
     }
 
-    /// Sets whether to log the node's output to Ziggurat's output stream.
+    /// Sets whether to log the node's output to Ziggurat's output stream, it's captured either
+    /// way (see [`Node::wait_for_log`]).
     pub fn log_to_stdout(&mut self, log_to_stdout: bool) -> &mut Self {
         self.config.log_to_stdout = log_to_stdout;
         self
     }
 
+    /// Sets whether to keep the node's data directory once it's stopped (e.g. to inspect its
+    /// logs), it's removed by default.
+    pub fn keep_data_dir(&mut self, keep_data_dir: bool) -> &mut Self {
+        self.config.keep_data_dir = keep_data_dir;
+        self
+    }
+
+    /// Sets when the node is considered ready by [`Node::start`], defaults to
+    /// [`Readiness::Listening`].
+    pub fn readiness(&mut self, readiness: Readiness) -> &mut Self {
+        self.config.readiness = readiness;
+        self
+    }
+
+    /// Sets the time allowed for the node to become ready, defaults to 60 seconds.
+    pub fn startup_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.startup_timeout = timeout;
+        self
+    }
+
+    /// Sets the time allowed for the node to shut down once terminated by [`Node::stop`], before
+    /// it's killed. Defaults to 10 seconds.
+    pub fn shutdown_grace(&mut self, grace: Duration) -> &mut Self {
+        self.config.shutdown_grace = grace;
+        self
+    }
+
+    /// Sets whether [`Node::stop`] fails if the node doesn't shut down cleanly (see
+    /// [`StopReport::is_clean`]).
+    pub fn require_clean_shutdown(&mut self, require: bool) -> &mut Self {
+        self.config.require_clean_shutdown = require;
+        self
+    }
+
+    /// Sets whether to enable the node's JSON-RPC server, on a free port of localhost picked on
+    /// start (see [`Node::rpc_client`]). It's ignored for custom nodes.
+    pub fn enable_rpc(&mut self, enable: bool) -> &mut Self {
+        self.config.rpc = enable;
+        self
+    }
+
+    /// Adds a line to zcashd's configuration file, e.g. `banscore=10`, taking precedence over the
+    /// generated configuration and the lines from `config.toml`. It's ignored for other nodes.
+    pub fn extra_config_line(&mut self, line: impl Into<String>) -> &mut Self {
+        self.config.extra_config_lines.push(line.into());
+        self
+    }
+
+    /// Overrides a value of zebra's configuration file, given by its dotted `key` (e.g.
+    /// `network.crawl_new_peer_interval`), taking precedence over the generated configuration and
+    /// the overrides from `config.toml`. It's ignored for other nodes.
+    pub fn config_override(&mut self, key: &str, value: impl Into<toml::Value>) -> &mut Self {
+        let value = key.rsplit('.').fold(value.into(), |value, key| {
+            let mut table = toml::value::Table::new();
+            table.insert(key.to_owned(), value);
+            toml::Value::Table(table)
+        });
+        if let toml::Value::Table(overrides) = value {
+            merge_tables(&mut self.config.config_overrides, &overrides);
+        }
+
+        self
+    }
+
+    /// Returns a client for the node's JSON-RPC server, `None` if it isn't enabled (see
+    /// [`Node::enable_rpc`]) or the node hasn't been started.
+    pub fn rpc_client(&self) -> Option<RpcClient> {
+        let rpc_addr = self.config.rpc_addr?;
+        let credentials = match self.meta.kind {
+            NodeKind::Zcashd => Some((RPC_USER, RPC_PASSWORD)),
+            // Zebra's RPC server doesn't use authentication.
+            NodeKind::Zebra => None,
+            // The RPC server of a custom node isn't configured by Ziggurat.
+            NodeKind::Custom => return None,
+        };
+
+        Some(RpcClient::new(rpc_addr, credentials))
+    }
+
     /// Sets the initial action to undertake once the node has started. See [`Action`] for more
     /// information on what the actions pertain.
     pub fn initial_action(&mut self, action: Action) -> &mut Self {
@@ -109,25 +310,30 @@ impl Node {
     /// Starts the node instance.
     ///
     /// This function will write the appropriate configuration file and run the start command
-    /// provided in `config.toml`.
+    /// provided in `config.toml`, then wait for the node to be ready (see [`Node::readiness`]).
+    ///
+    /// Fails if the node exits before being ready, or isn't ready in time (it's killed in that
+    /// case), the error includes the last lines of the node's log. The data directory is kept
+    /// for inspection.
     pub async fn start(&mut self) -> io::Result<()> {
         // cleanup any previous runs (node.stop won't always be reached e.g. test panics, or SIGINT)
         self.cleanup()?;
+        fs::create_dir_all(&self.config.path)?;
 
         // Setup the listener if there is some initial action required
         let synthetic_node = match self.config.initial_action {
             Action::None => None,
             Action::WaitForConnection | Action::SeedWithTestnetBlocks(_) => {
                 // Start a synthetic node to perform the initial actions.
-                let synthetic_node = SyntheticNode::builder()
+                let mut builder = SyntheticNode::builder()
                     .with_full_handshake()
-                    .with_message_filter(
-                        MessageFilter::with_all_auto_reply()
-                            .with_getheaders_filter(Filter::Disabled)
-                            .with_getdata_filter(Filter::Disabled),
-                    )
-                    .build()
-                    .await?;
+                    .with_all_auto_reply();
+
+                if let Action::SeedWithTestnetBlocks(block_count) = self.config.initial_action {
+                    builder = builder.with_chain(Chain::testnet(block_count));
+                }
+
+                let synthetic_node = builder.build().await?;
 
                 self.config
                     .initial_peers
@@ -137,24 +343,43 @@ impl Node {
             }
         };
 
+        if self.config.rpc && self.config.rpc_addr.is_none() {
+            self.config.rpc_addr = Some(SocketAddr::new(self.addr().ip(), free_port()?));
+        }
+
         // Generate config files for Zebra or Zcashd node.
         self.generate_config_file()?;
 
-        let (stdout, stderr) = match self.config.log_to_stdout {
-            true => (Stdio::inherit(), Stdio::inherit()),
-            false => (Stdio::null(), Stdio::null()),
-        };
+        // Capture the output into a log file named after the test (the thread's name).
+        let test_name = thread::current()
+            .name()
+            .filter(|name| *name != "main")
+            .unwrap_or("node")
+            .to_owned();
+        let log = NodeLog::new(Some(&self.config.path.join(format!("{}.log", test_name))))?;
 
-        let process = Command::new(&self.meta.start_command)
+        let mut process = Command::new(&self.meta.start_command)
             .current_dir(&self.meta.path)
             .args(&self.meta.start_args)
             .stdin(Stdio::null())
-            .stdout(stdout)
-            .stderr(stderr)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
-            .expect("node failed to start");
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("couldn't run {:?}: {}", self.meta.start_command, e),
+                )
+            })?;
+
+        // Safe, as both are piped.
+        log.capture(process.stdout.take().unwrap(), self.config.log_to_stdout);
+        log.capture(process.stderr.take().unwrap(), self.config.log_to_stdout);
 
         self.process = Some(process);
+        self.log = Some(log.clone());
+
+        self.wait_until_ready(&log).await?;
 
         if let Some(synthetic_node) = synthetic_node {
             self.perform_initial_action(synthetic_node).await?;
@@ -163,6 +388,65 @@ impl Node {
         Ok(())
     }
 
+    /// Waits for the node to be ready, or its process to exit.
+    async fn wait_until_ready(&mut self, log: &NodeLog) -> io::Result<()> {
+        let regex = match self.config.readiness {
+            Readiness::Listening => None,
+            Readiness::LogLine(ref pattern) => Some(logs::compile(pattern)?),
+        };
+
+        let start = Instant::now();
+        let mut mark = LogMark::default();
+        loop {
+            // Safe, as the process was just started.
+            if let Some(status) = self.process.as_mut().unwrap().try_wait()? {
+                self.process = None;
+                // Let the capture catch up with the last lines.
+                log.wait_for_close(LOG_CLOSE_TIMEOUT);
+
+                return Err(startup_error(
+                    io::ErrorKind::Other,
+                    format!("node exited during startup with {}", status),
+                    log,
+                ));
+            }
+
+            let ready = match regex {
+                // Checked without connecting, so the node doesn't see any peer before the test's.
+                None => sockets::is_listening(
+                    self.process.as_ref().unwrap().id(),
+                    self.config.local_addr,
+                )?,
+                Some(ref regex) => {
+                    let (line, end) = log.find(mark, regex);
+                    mark = end;
+                    line.is_some()
+                }
+            };
+            if ready {
+                return Ok(());
+            }
+
+            if start.elapsed() > self.config.startup_timeout {
+                if let Some(mut process) = self.process.take() {
+                    process.kill()?;
+                    process.wait()?;
+                }
+
+                return Err(startup_error(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "node wasn't ready ({:?}) in {:?}",
+                        self.config.readiness, self.config.startup_timeout
+                    ),
+                    log,
+                ));
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
     async fn perform_initial_action(&self, mut synthetic_node: SyntheticNode) -> io::Result<()> {
         const TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -175,68 +459,40 @@ impl Node {
             Action::SeedWithTestnetBlocks(_) if self.meta.kind == NodeKind::Zebra => {
                 unimplemented!("zebra doesn't support block seeding");
             }
-            Action::SeedWithTestnetBlocks(block_count) => {
-                use crate::protocol::message::Message;
-
-                let genesis_block = Block::testnet_genesis();
-                // initial blocks, skipping genesis as it doesn't get sent
-                let blocks = Block::initial_testnet_blocks()
-                    .into_iter()
-                    .take(block_count)
-                    .skip(1)
-                    .collect::<Vec<_>>();
-
-                // respond to GetHeaders(Block[0])
-                let source = match synthetic_node.recv_message_timeout(TIMEOUT).await? {
-                    (source, Message::GetHeaders(locations)) => {
-                        // The request should be from the genesis hash onwards,
-                        // i.e. locator_hash = [genesis.hash], stop_hash = [0]
-                        assert_eq!(
-                            locations.block_locator_hashes,
-                            vec![genesis_block.double_sha256().unwrap()]
-                        );
-                        assert_eq!(locations.hash_stop, Hash::zeroed());
-
-                        // Reply with headers for the initial block headers
-                        let headers = blocks.iter().map(|block| block.header.clone()).collect();
-                        synthetic_node
-                            .send_direct_message(source, Message::Headers(Headers::new(headers)))?;
-
-                        source
-                    }
-
-                    (_, msg) => {
+            Action::SeedWithTestnetBlocks(_) => {
+                // The synthetic node serves the chain by itself, in whichever order the node asks
+                // for it. Wait until every block (the genesis block is already known) was served.
+                let chain = synthetic_node
+                    .chain()
+                    .expect("the seeding synthetic node is built with a chain");
+                let block_count = chain.blocks().len();
+                let start = Instant::now();
+                while !(1..block_count).all(|height| chain.has_served_block(height)) {
+                    if start.elapsed() > TIMEOUT {
                         return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("Expected GetHeaders but got: {:?}", msg),
+                            io::ErrorKind::TimedOut,
+                            format!(
+                                "the node didn't request the {} seeded blocks in {:?}",
+                                block_count - 1,
+                                TIMEOUT
+                            ),
                         ));
                     }
-                };
-
-                // respond to GetData(inv) for the initial blocks
-                match synthetic_node.recv_message_timeout(TIMEOUT).await? {
-                    (source, Message::GetData(inv)) => {
-                        // The request must be for the initial blocks
-                        let inv_hashes = blocks.iter().map(|block| block.inv_hash()).collect();
-                        let expected = Inv::new(inv_hashes);
-                        assert_eq!(inv, expected);
-
-                        // Send the blocks
-                        for block in blocks {
-                            synthetic_node
-                                .send_direct_message(source, Message::Block(Box::new(block)))?;
-                        }
-                    }
 
-                    (_, msg) => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("Expected GetData but got: {:?}", msg),
-                        ))
-                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
                 }
 
                 // Check that the node has received and processed all previous messages.
+                let source = synthetic_node
+                    .connected_peers()
+                    .first()
+                    .copied()
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::NotConnected,
+                            "the node disconnected while being seeded",
+                        )
+                    })?;
                 synthetic_node.ping_pong_timeout(source, TIMEOUT).await?;
             }
         }
@@ -247,83 +503,270 @@ impl Node {
         Ok(())
     }
 
-    /// Stops the node instance.
+    /// Stops the node instance, returns how it shut down (`None` if it wasn't running).
     ///
-    /// The stop command will only be run if provided in the `config.toml` file as it may not be
-    /// necessary to shutdown a node (killing the process is sometimes sufficient).
-    pub fn stop(&mut self) -> io::Result<()> {
-        if let Some(mut child) = self.process.take() {
-            // Stop node process, and check for crash
-            // (needs to happen before cleanup)
-            let crashed = match child.try_wait()? {
-                None => {
-                    child.kill()?;
-                    None
-                }
-                Some(exit_code) if exit_code.success() => {
-                    Some("but exited successfully somehow".to_string())
-                }
-                Some(exit_code) => Some(format!("crashed with {}", exit_code)),
-            };
+    /// The node is sent `SIGTERM` to go through its shutdown path, and is only killed if it
+    /// doesn't exit within the grace period (see [`Node::shutdown_grace`]).
+    ///
+    /// Fails if the node had already exited (crashed), or if it didn't shut down cleanly and a
+    /// clean shutdown is required (see [`Node::require_clean_shutdown`]). The data directory
+    /// (including the log) is kept in that case.
+    ///
+    /// Nodes should always be stopped with this before they're dropped, as dropping a running node
+    /// kills it without a graceful shutdown.
+    pub async fn stop(&mut self) -> io::Result<Option<StopReport>> {
+        let mut child = match self.process.take() {
+            Some(child) => child,
+            None => return Ok(None),
+        };
 
-            self.cleanup()?;
+        let report = match crash_message(&mut child)? {
+            Some(crash_msg) => Err(crash_msg),
+            None => Ok(terminate(&mut child, self.config.shutdown_grace).await?),
+        };
 
-            if let Some(crash_msg) = crashed {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Node exited early, {}", crash_msg),
-                ));
+        // Let the capture catch up with the final logs.
+        if let Some(ref log) = self.log {
+            log.closed(LOG_CLOSE_TIMEOUT).await;
+        }
+
+        self.stopped(report).map(Some)
+    }
+
+    /// Kills the node instance without waiting for it to shut down, for use in [`Drop`] which
+    /// mustn't block the runtime.
+    fn kill(&mut self) -> io::Result<Option<StopReport>> {
+        let mut child = match self.process.take() {
+            Some(child) => child,
+            None => return Ok(None),
+        };
+
+        let report = match crash_message(&mut child)? {
+            Some(crash_msg) => Err(crash_msg),
+            None => {
+                let start = Instant::now();
+                child.kill()?;
+                // The process is dead once killed, so waiting for it doesn't block for long.
+                Ok(StopReport::new(child.wait()?, true, start.elapsed()))
             }
+        };
+
+        self.stopped(report).map(Some)
+    }
+
+    /// Checks how the stopped node shut down (or the message of its crash), and cleans up if it
+    /// went as expected.
+    fn stopped(&self, report: Result<StopReport, String>) -> io::Result<StopReport> {
+        let result = match report {
+            Err(crash_msg) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Node exited early, {}", crash_msg),
+            )),
+            Ok(report) if self.config.require_clean_shutdown && !report.is_clean() => Err(
+                io::Error::other(format!("Node didn't shut down cleanly, {}", report)),
+            ),
+            Ok(report) => Ok(report),
+        };
+
+        // The data directory and log are kept if the node or the test failed.
+        if result.is_ok() && !self.config.keep_data_dir && !thread::panicking() {
+            self.cleanup()?;
         }
 
-        Ok(())
+        result
+    }
+
+    /// Returns the path of the node's log file, it's in the node's data directory and named after
+    /// the test. `None` if the node hasn't been started.
+    pub fn log_path(&self) -> Option<&Path> {
+        self.log.as_ref().and_then(|log| log.path())
+    }
+
+    /// Returns the mark of the current end of the node's log, to be used with
+    /// [`Node::logs_since`] and [`Node::wait_for_log_since`].
+    pub fn log_mark(&self) -> LogMark {
+        self.log.as_ref().map(|log| log.mark()).unwrap_or_default()
+    }
+
+    /// Returns the lines the node logged since the mark.
+    ///
+    /// Only the most recent lines are kept in memory, the older ones are only in the log file.
+    pub fn logs_since(&self, mark: LogMark) -> Vec<String> {
+        self.log
+            .as_ref()
+            .map(|log| log.since(mark))
+            .unwrap_or_default()
+    }
+
+    /// Waits for the node to log a line matching the regex `pattern` since it was started,
+    /// returns the line.
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] if no line matches in time, or
+    /// [`io::ErrorKind::InvalidInput`] if the pattern isn't a valid regex.
+    pub async fn wait_for_log(&self, pattern: &str, timeout: Duration) -> io::Result<String> {
+        self.wait_for_log_since(LogMark::default(), pattern, timeout)
+            .await
+    }
+
+    /// Waits for the node to log a line matching the regex `pattern` since the mark, see
+    /// [`Node::wait_for_log`].
+    pub async fn wait_for_log_since(
+        &self,
+        mark: LogMark,
+        pattern: &str,
+        timeout: Duration,
+    ) -> io::Result<String> {
+        match self.log {
+            Some(ref log) => log.wait_for(mark, pattern, timeout).await,
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "node hasn't been started",
+            )),
+        }
     }
 
     fn generate_config_file(&self) -> io::Result<()> {
-        let config_file_path = self.meta.kind.config_filepath(&self.config.path);
-        let content = match self.meta.kind {
-            NodeKind::Zebra => ZebraConfigFile::generate(&self.config)
+        let content = match (self.meta.kind, &self.meta.config_template) {
+            (NodeKind::Custom, Some(template)) => {
+                CustomConfigFile::generate(template, &self.config)
+            }
+            // Custom nodes are checked for a template when read from the configuration file.
+            (NodeKind::Custom, None) => unreachable!("custom node without a template"),
+            (NodeKind::Zebra, _) => ZebraConfigFile::generate(&self.config)
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-            NodeKind::Zcashd => ZcashdConfigFile::generate(&self.config),
+            (NodeKind::Zcashd, _) => ZcashdConfigFile::generate(&self.config),
         };
 
-        fs::write(config_file_path, content)
+        fs::write(&self.meta.config_file, content)
     }
 
+    /// Removes the data directory, including the configuration file and cache.
     fn cleanup(&self) -> io::Result<()> {
-        self.cleanup_config_file()?;
-        self.cleanup_cache()
-    }
-
-    fn cleanup_config_file(&self) -> io::Result<()> {
-        let path = self.meta.kind.config_filepath(&self.config.path);
-        match fs::remove_file(path) {
-            // File may not exist, so we suppress the error.
+        match fs::remove_dir_all(&self.config.path) {
+            // Directory may not exist, so we suppress the error.
             Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
             _ => Ok(()),
         }
     }
+}
 
-    fn cleanup_cache(&self) -> io::Result<()> {
-        // Zebra doesn't currently use a cache as it's configured in ephemeral mode.
-        if let Some(path) = self.meta.kind.cache_path(&self.config.path) {
-            if let Err(e) = fs::remove_dir_all(path) {
-                // Directory may not exist, so we let that error through
-                if e.kind() != std::io::ErrorKind::NotFound {
-                    return Err(e);
-                }
-            }
+/// Returns how the node exited, if it already did (i.e. crashed).
+fn crash_message(child: &mut Child) -> io::Result<Option<String>> {
+    let crash_msg = match child.try_wait()? {
+        None => None,
+        Some(exit_code) if exit_code.success() => {
+            Some("but exited successfully somehow".to_string())
         }
+        Some(exit_code) => Some(format!("crashed with {}", exit_code)),
+    };
 
-        Ok(())
+    Ok(crash_msg)
+}
+
+/// Sends `SIGTERM` to the running process.
+fn send_sigterm(child: &Child) -> io::Result<()> {
+    // Safe, as the pid belongs to the child which hasn't been waited for yet.
+    if unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(io::Error::last_os_error());
     }
+
+    Ok(())
+}
+
+/// Sends `SIGTERM` to the running process, then `SIGKILL` if it doesn't exit within the grace
+/// period.
+async fn terminate(child: &mut Child, grace: Duration) -> io::Result<StopReport> {
+    let start = Instant::now();
+    send_sigterm(child)?;
+
+    while start.elapsed() < grace {
+        if let Some(status) = child.try_wait()? {
+            return Ok(StopReport::new(status, false, start.elapsed()));
+        }
+
+        tokio::time::sleep(TERMINATE_POLL_INTERVAL).await;
+    }
+
+    // The process is dead once killed, so waiting for it doesn't block for long.
+    child.kill()?;
+    let status = child.wait()?;
+
+    Ok(StopReport::new(status, true, start.elapsed()))
+}
+
+/// Constructs a startup error, with the last lines of the log.
+fn startup_error(kind: io::ErrorKind, msg: String, log: &NodeLog) -> io::Error {
+    let mut msg = format!("{}, last log lines:", msg);
+    for line in log.tail(STARTUP_LOG_TAIL) {
+        msg.push('\n');
+        msg.push_str(&line);
+    }
+
+    io::Error::new(kind, msg)
 }
 
 impl Drop for Node {
     fn drop(&mut self) {
-        // We should not panic in Drop
-        if let Err(err) = self.stop() {
+        // Print the tail of the log if the test is failing.
+        if let (true, Some(log)) = (thread::panicking(), &self.log) {
+            eprintln!(
+                "--- last {} lines of the node log ({}) ---",
+                FAILURE_LOG_TAIL,
+                log.path()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default()
+            );
+            for line in log.tail(FAILURE_LOG_TAIL) {
+                eprintln!("{}", line);
+            }
+        }
+
+        // Stopping gracefully could block the runtime for the whole grace period, so the node is
+        // killed instead. We should not panic in Drop.
+        if let Err(err) = self.kill() {
             error!("Failed to stop node: {}", err);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::Command;
+
+    #[tokio::test]
+    #[ignore]
+    async fn terminate_escalates_after_the_grace_period() {
+        // `sleep` doesn't handle SIGTERM, so it's terminated by the signal.
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let report = terminate(&mut child, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(
+            (report.exit_code, report.signal, report.killed),
+            (None, Some(libc::SIGTERM), false)
+        );
+        assert!(!report.is_clean());
+
+        // A process ignoring SIGTERM is killed.
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 10"])
+            .spawn()
+            .unwrap();
+        // Let the shell install the trap.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let report = terminate(&mut child, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!((report.signal, report.killed), (Some(libc::SIGKILL), true));
+        assert!(report.duration >= Duration::from_millis(100));
+
+        // A process exiting on SIGTERM shuts down cleanly.
+        let mut child = Command::new("sh")
+            .args(["-c", "trap 'exit 0' TERM; while true; do sleep 0.01; done"])
+            .spawn()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let report = terminate(&mut child, Duration::from_secs(5)).await.unwrap();
+        assert!(report.is_clean(), "{}", report);
+    }
+}
@@ -0,0 +1,139 @@
+//! Inspection of the TCP sockets of a node's process tree via `/proc`, so the node doesn't see any
+//! connection from the checks.
+
+use crate::tools::metrics::resources::with_descendants;
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+/// The `st` value of an established connection in `/proc/net/tcp`.
+const TCP_ESTABLISHED: &str = "01";
+/// The `st` value of a listening socket in `/proc/net/tcp`.
+const TCP_LISTEN: &str = "0A";
+
+/// Returns the remote addresses of the established TCP connections of the process and its
+/// descendants.
+pub(super) fn established_connections(pid: u32) -> io::Result<HashSet<SocketAddr>> {
+    Ok(tcp_sockets(pid, TCP_ESTABLISHED)?
+        .into_iter()
+        .map(|(_, remote)| remote)
+        .collect())
+}
+
+/// Indicates if the process or one of its descendants listens on the address (or on the
+/// unspecified address with the same port).
+pub(super) fn is_listening(pid: u32, addr: SocketAddr) -> io::Result<bool> {
+    Ok(tcp_sockets(pid, TCP_LISTEN)?.into_iter().any(|(local, _)| {
+        local.port() == addr.port() && (local.ip() == addr.ip() || local.ip().is_unspecified())
+    }))
+}
+
+/// Returns the local and remote addresses of the TCP sockets in the state (`st`) which belong to
+/// the process and its descendants.
+fn tcp_sockets(pid: u32, state: &str) -> io::Result<Vec<(SocketAddr, SocketAddr)>> {
+    let mut inodes = HashSet::new();
+    for pid in with_descendants(pid)? {
+        // The process may have exited in the meantime.
+        let fds = match fs::read_dir(format!("/proc/{}/fd", pid)) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+
+        for fd in fds.flatten() {
+            if let Ok(target) = fs::read_link(fd.path()) {
+                if let Some(inode) = target
+                    .to_str()
+                    .and_then(|target| target.strip_prefix("socket:["))
+                    .and_then(|target| target.strip_suffix(']'))
+                {
+                    inodes.insert(inode.to_owned());
+                }
+            }
+        }
+    }
+
+    let mut sockets = Vec::new();
+    for table in &["/proc/net/tcp", "/proc/net/tcp6"] {
+        let contents = match fs::read_to_string(table) {
+            Ok(contents) => contents,
+            // IPv6 may be disabled.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        // Fields: sl, local_address, rem_address, st, tx_queue:rx_queue, tr:tm->when, retrnsmt,
+        // uid, timeout, inode.
+        for fields in contents.lines().skip(1).map(|line| line.split_whitespace()) {
+            let fields: Vec<_> = fields.collect();
+            if fields.len() > 9 && fields[3] == state && inodes.contains(fields[9]) {
+                if let (Some(local), Some(remote)) = (
+                    parse_proc_net_addr(fields[1]),
+                    parse_proc_net_addr(fields[2]),
+                ) {
+                    sockets.push((local, remote));
+                }
+            }
+        }
+    }
+
+    Ok(sockets)
+}
+
+/// Parses an address of `/proc/net/tcp(6)`, e.g. `0100007F:1F90`. The IP is made of native
+/// endian 32-bit words, IPv4-mapped IPv6 addresses are returned as IPv4.
+fn parse_proc_net_addr(addr: &str) -> Option<SocketAddr> {
+    let (ip, port) = addr.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..ip.len()).step_by(8) {
+        let word = u32::from_str_radix(ip.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&bytes);
+            let ip = Ipv6Addr::from(octets);
+            match ip.to_ipv4_mapped() {
+                Some(ip) => IpAddr::V4(ip),
+                None => IpAddr::V6(ip),
+            }
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn established_connections_are_found_in_proc() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _stream = std::net::TcpStream::connect(addr).unwrap();
+
+        let connections = established_connections(std::process::id()).unwrap();
+        assert!(connections.contains(&addr));
+    }
+
+    #[test]
+    #[ignore]
+    fn listening_sockets_are_found_in_proc() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(is_listening(std::process::id(), addr).unwrap());
+
+        drop(listener);
+        assert!(!is_listening(std::process::id(), addr).unwrap());
+    }
+}
@@ -32,7 +32,7 @@ async fn when_node_receives_connection() {
 
     // Gracefully shut down the nodes.
     synthetic_node.shut_down();
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -59,5 +59,5 @@ async fn when_node_initiates_connection() {
 
     // Gracefully shut down the nodes.
     synthetic_node.shut_down();
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
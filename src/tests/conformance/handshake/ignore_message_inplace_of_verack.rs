@@ -139,22 +139,17 @@ mod when_node_receives_connection {
         synthetic_node.send_direct_message(node.addr(), Message::Verack)?;
 
         // Read Verack.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
-            Ok((_, Message::Verack)) => Ok(()),
-            Ok((_, unexpected)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Message was not ignored, received {}", unexpected),
-            )),
-            Err(_timeout) if !synthetic_node.is_connected(node.addr()) => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "Connection terminated",
-            )),
-            Err(err) => Err(err),
-        }?;
+        synthetic_node
+            .expect(
+                node.addr(),
+                |message| matches!(message, Message::Verack),
+                RECV_TIMEOUT,
+            )
+            .await?;
 
         // Gracefully shut down the nodes.
         synthetic_node.shut_down();
-        node.stop()?;
+        node.stop().await?;
 
         Ok(())
     }
@@ -286,22 +281,17 @@ mod when_node_initiates_connection {
         synthetic_node.send_direct_message(node_addr, Message::Verack)?;
 
         // Read Verack.
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await {
-            Ok((_, Message::Verack)) => Ok(()),
-            Ok((_, unexpected)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Message was not ignored, received {}", unexpected),
-            )),
-            Err(_timeout) if !synthetic_node.is_connected(node.addr()) => Err(io::Error::new(
-                io::ErrorKind::ConnectionAborted,
-                "Connection terminated",
-            )),
-            Err(err) => Err(err),
-        }?;
+        synthetic_node
+            .expect(
+                node_addr,
+                |message| matches!(message, Message::Verack),
+                RECV_TIMEOUT,
+            )
+            .await?;
 
         // Gracefully shut down the nodes.
         synthetic_node.shut_down();
-        node.stop()?;
+        node.stop().await?;
 
         Ok(())
     }
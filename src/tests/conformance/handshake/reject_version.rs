@@ -45,7 +45,7 @@ async fn reusing_nonce() {
 
     // Gracefully shut down the nodes.
     synthetic_node.shut_down();
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -97,5 +97,5 @@ async fn with_obsolete_version_numbers() {
     }
 
     // Gracefully shut down the node.
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
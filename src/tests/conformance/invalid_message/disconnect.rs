@@ -86,7 +86,7 @@ async fn pong_with_wrong_nonce() {
     }
 
     synthetic_node.shut_down();
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -185,23 +185,23 @@ async fn run_test_case_bytes(bytes: Vec<u8>) -> io::Result<()> {
         .await
     {
         Err(ConnectionAborted) => Ok(()),
-        Ok(_) => Err(io::Error::new(io::ErrorKind::Other, "Message was ignored")),
-        Err(Unexpected(msg)) => Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Message was replied to with {}.", msg),
-        )),
+        Ok(_) => Err(io::Error::other("Message was ignored")),
+        Err(Unexpected(msg)) => Err(io::Error::other(format!(
+            "Message was replied to with {}.",
+            msg
+        ))),
         Err(Timeout(_)) => Err(io::Error::new(
             io::ErrorKind::TimedOut,
             "Timeout waiting for disconnect.",
         )),
-        Err(err) => Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Error waiting for disconnect: {:?}", err),
-        )),
+        Err(err) => Err(io::Error::other(format!(
+            "Error waiting for disconnect: {:?}",
+            err
+        ))),
     };
 
     synthetic_node.shut_down();
-    node.stop()?;
+    node.stop().await?;
 
     result
 }
@@ -118,23 +118,20 @@ async fn run_test_case(message: Message, expected_code: CCode) -> io::Result<()>
         .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
         .await
     {
-        Ok(_) => Err(io::Error::new(io::ErrorKind::Other, "Message was ignored")),
+        Ok(_) => Err(io::Error::other("Message was ignored")),
         Err(PingPongError::Unexpected(msg)) => match *msg {
             Message::Reject(reject) if reject.ccode == expected_code => Ok(()),
             Message::Reject(reject) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!(
-                        "Incorrect rejection ccode: {:?} instead of {:?}",
-                        reject.ccode, expected_code
-                    ),
-                ))
+                return Err(io::Error::other(format!(
+                    "Incorrect rejection ccode: {:?} instead of {:?}",
+                    reject.ccode, expected_code
+                )))
             }
             unexpected => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Unexpected message received: {:?}", unexpected),
-                ))
+                return Err(io::Error::other(format!(
+                    "Unexpected message received: {:?}",
+                    unexpected
+                )))
             }
         },
         Err(err) => Err(err.into()),
@@ -142,7 +139,7 @@ async fn run_test_case(message: Message, expected_code: CCode) -> io::Result<()>
 
     // clean-up
     synthetic_node.shut_down();
-    node.stop()?;
+    node.stop().await?;
 
     result
 }
@@ -95,7 +95,7 @@ async fn eagerly_crawls_network_for_peers() {
     }
 
     // Gracefully shut down the node.
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -169,5 +169,5 @@ async fn correctly_lists_peers() {
         synthetic_node.shut_down();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -131,14 +131,14 @@ mod node_is_seeded_with_blocks {
             .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
             .await
         {
-            Ok(_) => Err(io::Error::new(io::ErrorKind::Other, "Query was ignored")),
+            Ok(_) => Err(io::Error::other("Query was ignored")),
             Err(PingPongError::Unexpected(msg)) => Ok(*msg),
             Err(err) => Err(err.into()),
         };
 
         // Gracefully shut down the nodes.
         synthetic_node.shut_down();
-        node.stop()?;
+        node.stop().await?;
 
         result
     }
@@ -244,14 +244,14 @@ mod node_is_not_seeded_with_blocks {
             .ping_pong_timeout(node.addr(), RECV_TIMEOUT)
             .await
         {
-            Ok(_) => Err(io::Error::new(io::ErrorKind::Other, "Query was ignored")),
+            Ok(_) => Err(io::Error::other("Query was ignored")),
             Err(PingPongError::Unexpected(msg)) => Ok(*msg),
             Err(err) => Err(err.into()),
         };
 
         // Gracefully shut down the nodes.
         synthetic_node.shut_down();
-        node.stop()?;
+        node.stop().await?;
 
         result
     }
@@ -81,7 +81,7 @@ mod multiple_blocks {
     #[tokio::test]
     async fn out_of_order() {
         // zcashd: pass
-        let blocks = vec![&SEED_BLOCKS[3], &SEED_BLOCKS[1], &SEED_BLOCKS[7]];
+        let blocks = [&SEED_BLOCKS[3], &SEED_BLOCKS[1], &SEED_BLOCKS[7]];
         let inv_hash = blocks.iter().map(|block| block.inv_hash()).collect();
         let query = Message::GetData(Inv::new(inv_hash));
         let expected = blocks
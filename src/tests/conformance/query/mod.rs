@@ -1,4 +1,4 @@
-use std::{io, time::Duration};
+use std::io;
 
 use crate::{
     protocol::{
@@ -6,7 +6,7 @@ use crate::{
         payload::{block::Block, Nonce},
     },
     setup::node::{Action, Node},
-    tools::synthetic_node::SyntheticNode,
+    tools::{chain::Chain, synthetic_node::SyntheticNode, TIMEOUT},
 };
 
 mod basic_query;
@@ -31,10 +31,12 @@ async fn run_test_query(query: Message) -> io::Result<Vec<Message>> {
         .start()
         .await?;
 
-    // Create a synthetic node.
+    // Create a synthetic node which serves the same chain, in case the node queries it.
     let mut synthetic_node = SyntheticNode::builder()
         .with_full_handshake()
         .with_all_auto_reply()
+        .with_chain(Chain::testnet(SEED_BLOCKS.len()))
+        .with_skip_limit(usize::MAX)
         .build()
         .await?;
 
@@ -49,18 +51,17 @@ async fn run_test_query(query: Message) -> io::Result<Vec<Message>> {
     synthetic_node.send_direct_message(node.addr(), Message::Ping(nonce))?;
 
     // Receive messages until we receive the matching Pong, or we timeout.
-    const RECV_TIMEOUT: Duration = Duration::from_millis(100);
-    let mut messages = Vec::new();
-    loop {
-        match synthetic_node.recv_message_timeout(RECV_TIMEOUT).await? {
-            (_, Message::Pong(rx_nonce)) if rx_nonce == nonce => break,
-            (_, message) => messages.push(message),
-        }
-    }
+    let pong = synthetic_node
+        .expect(
+            node.addr(),
+            |message| matches!(message, Message::Pong(rx_nonce) if *rx_nonce == nonce),
+            TIMEOUT,
+        )
+        .await?;
 
     // Gracefully shut down the nodes.
     synthetic_node.shut_down();
-    node.stop()?;
+    node.stop().await?;
 
-    Ok(messages)
+    Ok(pong.skipped)
 }
@@ -5,7 +5,7 @@ use crate::{
     tools::{
         metrics::{
             recorder,
-            tables::{fmt_table, table_float_display},
+            tables::{fmt_table, table_float_display, ResourceStats},
         },
         synthetic_node::SyntheticNode,
     },
@@ -33,6 +33,8 @@ struct Stats {
     #[header("\n time (s) ")]
     #[field(display_with = "table_float_display")]
     pub time: f64,
+    #[header(inline)]
+    pub resources: ResourceStats,
 }
 
 impl Stats {
@@ -109,7 +111,7 @@ async fn load_bearing() {
     //
 
     // setup metrics recorder
-    recorder::enable_simple_recorder().unwrap();
+    let _recorder = recorder::enable_exclusive().await;
 
     // maximum time allowed for a single iteration of the test
     const MAX_ITER_TIME: Duration = Duration::from_secs(20);
@@ -117,6 +119,9 @@ async fn load_bearing() {
     /// maximum peers to configure node with
     const MAX_PEERS: u16 = 50;
 
+    /// interval at which the node's resource usage is sampled
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
     let synth_counts = vec![100u16, 1_000, 5_000, 10_000, 15_000, 20_000];
 
     let mut all_stats = Vec::new();
@@ -128,6 +133,7 @@ async fn load_bearing() {
         .start()
         .await
         .unwrap();
+    let sampler = node.resource_sampler(SAMPLE_INTERVAL).unwrap();
 
     for synth_count in synth_counts {
         // clear and register metrics
@@ -143,6 +149,7 @@ async fn load_bearing() {
             tokio::sync::mpsc::channel::<()>(synth_count as usize);
 
         let test_start = tokio::time::Instant::now();
+        sampler.reset();
 
         // start synthetic nodes
         for _ in 0..synth_count {
@@ -184,6 +191,7 @@ async fn load_bearing() {
         // Collect stats for this run
         let mut stats = Stats::new(MAX_PEERS, synth_count);
         stats.time = test_start.elapsed().as_secs_f64();
+        stats.resources = ResourceStats::new(&sampler.samples());
         {
             let counters = recorder::counters();
             let counters_lock = counters.lock();
@@ -208,7 +216,7 @@ async fn load_bearing() {
         all_stats.push(stats);
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 
     // Display results table
     println!("{}", fmt_table(Table::new(&all_stats)));
@@ -9,7 +9,7 @@ use crate::{
     setup::node::{Action, Node},
     tools::{
         metrics::{
-            recorder::{self, enable_simple_recorder},
+            recorder,
             tables::{duration_as_ms, RequestStats, RequestsTable},
         },
         synthetic_node::SyntheticNode,
@@ -74,7 +74,7 @@ async fn throughput() {
     // └───────┴──────────┴──────────┴──────────┴──────────────┴──────────┴──────────┴──────────┴──────────┴──────────┴──────────────┴──────────┴────────────┘
 
     // setup metrics recorder
-    enable_simple_recorder().unwrap();
+    let _recorder = recorder::enable_exclusive().await;
 
     // number of requests to send per peer
     const REQUESTS: usize = 100;
@@ -175,7 +175,7 @@ async fn throughput() {
         ));
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 
     // Display various percentiles
     println!("{}", table);
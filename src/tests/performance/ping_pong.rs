@@ -1,15 +1,14 @@
 use std::{net::SocketAddr, time::Duration};
 
-use recorder::enable_simple_recorder;
-
 use crate::{
     protocol::{message::Message, payload::Nonce},
     setup::node::{Action, Node},
     tools::{
         metrics::{
             recorder,
-            tables::{duration_as_ms, RequestStats, RequestsTable},
+            tables::{RequestStats, RequestsTable},
         },
+        swarm::{Peer, SwarmBuilder},
         synthetic_node::SyntheticNode,
     },
 };
@@ -114,7 +113,7 @@ async fn throughput() {
     // └───────┴──────────┴──────────┴──────────┴──────────────┴──────────┴──────────┴──────────┴──────────┴──────────┴──────────────┴──────────┴────────────┘
 
     // setup metrics recorder
-    enable_simple_recorder().unwrap();
+    let _recorder = recorder::enable_exclusive().await;
 
     // number of concurrent peers to test (zcashd hardcaps `max_peers` to 873 on my machine)
     let synth_counts = vec![
@@ -134,23 +133,22 @@ async fn throughput() {
     let node_addr = node.addr();
 
     for synth_count in synth_counts {
-        // clear metrics and register metrics
+        // clear metrics, the swarm registers its own
         recorder::clear();
-        metrics::register_histogram!(METRIC_LATENCY);
 
         // create N peer nodes which send M ping's as fast as possible
-        let mut synth_handles = Vec::with_capacity(synth_count);
-        let test_start = tokio::time::Instant::now();
-        for _ in 0..synth_count {
-            synth_handles.push(tokio::spawn(simulate_peer(node_addr)));
-        }
+        let swarm = SwarmBuilder::new(
+            SyntheticNode::builder()
+                .with_full_handshake()
+                .with_all_auto_reply(),
+            synth_count,
+        )
+        .with_latency_metric(METRIC_LATENCY)
+        .start(move |peer| simulate_peer(peer, node_addr));
 
         // wait for peers to complete
-        for handle in synth_handles {
-            let _ = handle.await;
-        }
-
-        let time_taken_secs = test_start.elapsed().as_secs_f64();
+        let (_, time_taken) = swarm.join().await;
+        let time_taken_secs = time_taken.as_secs_f64();
 
         // get latency stats
         let latencies = recorder::histograms()
@@ -169,39 +167,29 @@ async fn throughput() {
         ));
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 
     // Display results table
     println!("{}", table);
 }
 
-async fn simulate_peer(node_addr: SocketAddr) {
-    // Create a synthetic node, enable handshaking and auto-reply
-    let mut synth_node = SyntheticNode::builder()
-        .with_full_handshake()
-        .with_all_auto_reply()
-        .build()
-        .await
-        .unwrap();
-    synth_node.connect(node_addr).await.unwrap();
+async fn simulate_peer(mut peer: Peer, node_addr: SocketAddr) {
+    peer.node.connect(node_addr).await.unwrap();
 
     for _ in 0..PINGS {
         let nonce = Nonce::default();
         let expected = Message::Pong(nonce);
 
         // send Ping(nonce)
-        synth_node
+        peer.node
             .send_direct_message(node_addr, Message::Ping(nonce))
             .unwrap();
 
         let now = tokio::time::Instant::now();
-        match synth_node
-            .recv_message_timeout(Duration::from_secs(5))
-            .await
-        {
+        match peer.node.recv_message_timeout(Duration::from_secs(5)).await {
             Ok((_, reply)) => {
                 assert_eq!(reply, expected);
-                metrics::histogram!(METRIC_LATENCY, duration_as_ms(now.elapsed()));
+                peer.record_latency(now.elapsed());
             }
             Err(_timeout) => break,
         }
@@ -50,7 +50,7 @@ async fn instead_of_version_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -90,7 +90,7 @@ async fn instead_of_verack_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -149,7 +149,7 @@ async fn instead_of_version_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -211,7 +211,7 @@ async fn instead_of_verack_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -251,5 +251,5 @@ async fn post_handshake() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -50,7 +50,7 @@ async fn instead_of_version_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -90,7 +90,7 @@ async fn instead_of_verack_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -150,7 +150,7 @@ async fn instead_of_version_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -215,7 +215,7 @@ async fn instead_of_verack_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -254,5 +254,5 @@ async fn post_handshake() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -46,7 +46,7 @@ async fn instead_of_version_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -84,7 +84,7 @@ async fn instead_of_verack_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -144,7 +144,7 @@ async fn instead_of_version_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -207,7 +207,7 @@ async fn instead_of_verack_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -245,5 +245,5 @@ async fn post_handshake() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -46,7 +46,7 @@ async fn instead_of_version_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -84,7 +84,7 @@ async fn instead_of_verack_when_node_receives_connection() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -145,7 +145,7 @@ async fn instead_of_version_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -210,7 +210,7 @@ async fn instead_of_verack_when_node_initiates_connection() {
         handle.await.unwrap().unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -248,5 +248,5 @@ async fn post_handshake() {
             .is_ok());
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
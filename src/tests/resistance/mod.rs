@@ -1,6 +1,7 @@
 mod corrupt_message;
 mod random_bytes;
 mod stress_test;
+mod unread_replies;
 mod zeroes;
 
 use std::time::Duration;
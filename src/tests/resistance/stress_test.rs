@@ -22,7 +22,10 @@ use crate::{
         },
         metrics::{
             recorder,
-            tables::{duration_as_ms, fmt_table, table_float_display, RequestStats, RequestsTable},
+            tables::{
+                duration_as_ms, fmt_table, table_float_display, RequestStats, RequestsTable,
+                ResourceStats,
+            },
         },
         synthetic_node::SyntheticNode,
     },
@@ -54,6 +57,8 @@ struct Stats {
     #[header(" time (s) ")]
     #[field(display_with = "table_float_display")]
     time: f64,
+    #[header(inline)]
+    resources: ResourceStats,
 }
 
 const REQUEST_LATENCY: &str = "fuzz_flood_request_latency";
@@ -200,9 +205,11 @@ async fn throughput() {
     // └───────┴──────────┴──────────┴──────────┴──────────────┴──────────┴──────────┴──────────┴──────────┴──────────┴──────────────┴──────────┴────────────┘
 
     // enable simple metrics recording
-    recorder::enable_simple_recorder().unwrap();
+    let _recorder = recorder::enable_exclusive().await;
 
     const TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(20);
+    // Interval at which the node's resource usage is sampled.
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
 
     let mut request_table = RequestsTable::default();
     let mut handshake_table = RequestsTable::default();
@@ -228,12 +235,14 @@ async fn throughput() {
         .unwrap();
 
     let node_addr = node.addr();
+    let sampler = node.resource_sampler(SAMPLE_INTERVAL).unwrap();
 
     // Iterate over peer counts
     // (note: rng usage should stay in a single thread in order for it be somewhat repeatable - we can't account
     // for relative timings and state transitions in the node).
     for peers in synth_counts {
         let iteration_timer = tokio::time::Instant::now();
+        sampler.reset();
         // register metrics
         recorder::clear();
         metrics::register_histogram!(REQUEST_LATENCY);
@@ -329,6 +338,7 @@ async fn throughput() {
             requests: MAX_VALID_MESSAGES,
             time: iteration_time,
             dangling: peers as u16 - completed,
+            resources: ResourceStats::new(&sampler.samples()),
             ..Default::default()
         };
         {
@@ -379,7 +389,7 @@ async fn throughput() {
     println!("Request latencies\n{}\n", request_table);
     println!("Handshake latencies\n{}\n", handshake_table);
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 // A list of valid queries and their expected responses
@@ -0,0 +1,111 @@
+//! Contains tests where a peer floods the node with requests without reading the replies.
+
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{block::Block, Inv},
+    },
+    setup::node::{Action, Node},
+    tools::synthetic_node::SyntheticNode,
+};
+
+use assert_matches::assert_matches;
+use tokio::time::{sleep, Duration, Instant};
+
+/// How the node handled a peer which doesn't read its replies.
+#[derive(Debug)]
+enum Outcome {
+    /// The node disconnected the peer.
+    Disconnected,
+    /// The node stopped reading the peer's requests.
+    StoppedReading,
+    /// The node kept reading the peer's requests for the whole time limit.
+    StillReading,
+}
+
+#[tokio::test]
+async fn getdata_blocks_without_reading_replies() {
+    // ZG-RESISTANCE-007
+    //
+    // The node bounds the replies queued up for a peer which doesn't read them.
+    //
+    // We keep requesting all of the initial testnet blocks, while the replies are left unread.
+    // Once the receive and send buffers are full, the node must either disconnect us or stop
+    // reading our requests (our writes stall). A node which keeps reading (and queueing up the
+    // replies) for the whole time limit fails the test.
+    //
+    // Note: the results are printed to help gauge the node's limits, run with
+    //       `cargo test tests::resistance::unread_replies -- --nocapture`
+
+    const TIME_LIMIT: Duration = Duration::from_secs(60);
+    // The node is considered to have stopped reading once none of our writes succeed for this long.
+    const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+    const SLEEP: Duration = Duration::from_millis(10);
+
+    let blocks = Block::initial_testnet_blocks();
+    let request = Message::GetData(Inv::new(
+        blocks.iter().map(|block| block.inv_hash()).collect(),
+    ));
+
+    // The size of the replies to a single request.
+    let reply_bytes = blocks
+        .into_iter()
+        .map(|block| {
+            let mut bytes = Vec::new();
+            Message::Block(Box::new(block)).encode(&mut bytes).unwrap();
+            bytes.len()
+        })
+        .sum::<usize>();
+
+    let mut node = Node::new().unwrap();
+    node.initial_action(Action::SeedWithTestnetBlocks(11))
+        .start()
+        .await
+        .unwrap();
+
+    let synthetic_node = SyntheticNode::builder()
+        .with_full_handshake()
+        .with_all_auto_reply()
+        .build()
+        .await
+        .unwrap();
+    synthetic_node.connect(node.addr()).await.unwrap();
+    synthetic_node.pause_reading(node.addr());
+
+    let start = Instant::now();
+    let mut last_write = start;
+    let mut requests = 0usize;
+
+    let outcome = loop {
+        if !synthetic_node.is_connected(node.addr()) {
+            break Outcome::Disconnected;
+        }
+
+        if start.elapsed() >= TIME_LIMIT {
+            break Outcome::StillReading;
+        }
+
+        // Writing fails while our outbound queue is full, i.e. the node isn't reading.
+        match synthetic_node.send_direct_message(node.addr(), request.clone()) {
+            Ok(()) => {
+                requests += 1;
+                last_write = Instant::now();
+            }
+            Err(_) if last_write.elapsed() >= STALL_TIMEOUT => break Outcome::StoppedReading,
+            Err(_) => sleep(SLEEP).await,
+        }
+    };
+
+    println!(
+        "outcome: {:?}, after: {:.2}s, requests: {}, replies requested: {:.2} MB",
+        outcome,
+        last_write.duration_since(start).as_secs_f64(),
+        requests,
+        (requests * reply_bytes) as f64 / 1_000_000.0
+    );
+
+    synthetic_node.shut_down();
+    node.stop().await.unwrap();
+
+    assert_matches!(outcome, Outcome::Disconnected | Outcome::StoppedReading);
+}
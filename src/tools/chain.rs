@@ -0,0 +1,336 @@
+//! An in-memory block store which can serve chain data to peers.
+
+use parking_lot::Mutex;
+
+use crate::protocol::{
+    message::Message,
+    payload::{
+        block::{Block, Headers, LocatorHashes},
+        inv::ObjectKind,
+        Hash, Inv, Tx,
+    },
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// Maximum number of headers sent in reply to a single [`GetHeaders`](Message::GetHeaders).
+pub const MAX_HEADERS: usize = 160;
+/// Maximum number of inventory hashes sent in reply to a single [`GetBlocks`](Message::GetBlocks).
+pub const MAX_BLOCKS_INV: usize = 500;
+
+/// An in-memory chain of blocks (starting with the genesis block) and a memory pool of
+/// transactions.
+///
+/// Replies to chain queries statelessly, so it doesn't matter in what order or how often a peer
+/// asks for data:
+/// - [`GetHeaders`] gets the [`Headers`] following the first known locator hash, up to and
+///   including the stop hash,
+/// - [`GetBlocks`] gets an [`Inv`] of the blocks following the first known locator hash, up to
+///   (excluding) the stop hash, no reply is sent if there are no such blocks,
+/// - [`GetData`] gets a [`Block`] or [`Tx`] for every known object in order, followed by a single
+///   [`NotFound`] for all of the unknown objects,
+/// - [`MemPool`] gets an [`Inv`] of the transactions in the memory pool.
+///
+/// [`GetHeaders`]: Message::GetHeaders
+/// [`Headers`]: Message::Headers
+/// [`GetBlocks`]: Message::GetBlocks
+/// [`Inv`]: Message::Inv
+/// [`GetData`]: Message::GetData
+/// [`Block`]: Message::Block
+/// [`Tx`]: Message::Tx
+/// [`NotFound`]: Message::NotFound
+/// [`MemPool`]: Message::MemPool
+#[derive(Debug)]
+pub struct Chain {
+    blocks: Vec<Block>,
+    /// Block heights indexed by block hash.
+    heights: HashMap<Hash, usize>,
+    /// Transactions from the blocks and the memory pool indexed by their hash.
+    txs: HashMap<Hash, Tx>,
+    /// Hashes of the transactions in the memory pool.
+    mempool: Vec<Hash>,
+    /// Heights of the blocks which were served in reply to a [`GetData`](Message::GetData).
+    served: Mutex<HashSet<usize>>,
+}
+
+impl Chain {
+    /// Creates a chain from the given blocks, which must be ordered by height starting from the
+    /// genesis block.
+    pub fn new(blocks: Vec<Block>) -> Self {
+        let mut heights = HashMap::with_capacity(blocks.len());
+        let mut txs = HashMap::new();
+
+        for (height, block) in blocks.iter().enumerate() {
+            heights.insert(block.double_sha256().unwrap(), height);
+
+            for tx in &block.txs {
+                txs.insert(tx.double_sha256().unwrap(), tx.clone());
+            }
+        }
+
+        Self {
+            blocks,
+            heights,
+            txs,
+            mempool: Vec::new(),
+            served: Default::default(),
+        }
+    }
+
+    /// Creates a chain of the first `n` testnet blocks (see [`Block::initial_testnet_blocks`]).
+    pub fn testnet(n: usize) -> Self {
+        Self::new(
+            Block::initial_testnet_blocks()
+                .into_iter()
+                .take(n)
+                .collect(),
+        )
+    }
+
+    /// Sets the transactions in the memory pool.
+    pub fn with_mempool(mut self, txs: Vec<Tx>) -> Self {
+        self.mempool.clear();
+
+        for tx in txs {
+            let hash = tx.double_sha256().unwrap();
+            self.mempool.push(hash);
+            self.txs.insert(hash, tx);
+        }
+
+        self
+    }
+
+    /// Returns the blocks in the chain, ordered by height.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Returns `true` if the block at `height` has been served to a peer.
+    pub fn has_served_block(&self, height: usize) -> bool {
+        self.served.lock().contains(&height)
+    }
+
+    /// Returns the replies to the message, which are empty for non-chain messages.
+    pub fn reply(&self, message: &Message) -> Vec<Message> {
+        match message {
+            Message::GetHeaders(locator) => vec![Message::Headers(self.headers(locator))],
+            Message::GetBlocks(locator) => {
+                let inv = self.block_inv(locator);
+                if inv.inventory.is_empty() {
+                    vec![]
+                } else {
+                    vec![Message::Inv(inv)]
+                }
+            }
+            Message::GetData(inv) => self.get_data(inv),
+            Message::MemPool => vec![Message::Inv(self.mempool_inv())],
+            _ => vec![],
+        }
+    }
+
+    /// Returns the headers requested by the locator.
+    pub fn headers(&self, locator: &LocatorHashes) -> Headers {
+        // Without a locator only the header of the stop hash block is requested.
+        if locator.block_locator_hashes.is_empty() {
+            let headers = self
+                .heights
+                .get(&locator.hash_stop)
+                .map(|&height| vec![self.blocks[height].header.clone()])
+                .unwrap_or_default();
+
+            return Headers::new(headers);
+        }
+
+        let start = self.fork_height(locator) + 1;
+        let end = self
+            .heights
+            .get(&locator.hash_stop)
+            .map_or(self.blocks.len(), |&height| height + 1)
+            .min(start + MAX_HEADERS);
+
+        let headers = self
+            .blocks
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|block| block.header.clone())
+            .collect();
+
+        Headers::new(headers)
+    }
+
+    /// Returns the inventory of the blocks requested by the locator.
+    pub fn block_inv(&self, locator: &LocatorHashes) -> Inv {
+        let start = self.fork_height(locator) + 1;
+        let end = self
+            .heights
+            .get(&locator.hash_stop)
+            .map_or(self.blocks.len(), |&height| height)
+            .min(start + MAX_BLOCKS_INV);
+
+        let inventory = self
+            .blocks
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|block| block.inv_hash())
+            .collect();
+
+        Inv::new(inventory)
+    }
+
+    /// Returns the blocks and transactions requested by the inventory, followed by a
+    /// [`NotFound`](Message::NotFound) for the missing objects (if any).
+    pub fn get_data(&self, inv: &Inv) -> Vec<Message> {
+        let mut replies = Vec::with_capacity(inv.inventory.len());
+        let mut missing = Vec::new();
+
+        for inv_hash in &inv.inventory {
+            match inv_hash.kind() {
+                ObjectKind::Block => match self.heights.get(inv_hash.hash()) {
+                    Some(&height) => {
+                        self.served.lock().insert(height);
+                        replies.push(Message::Block(Box::new(self.blocks[height].clone())));
+                    }
+                    None => missing.push(*inv_hash),
+                },
+                ObjectKind::Tx => match self.txs.get(inv_hash.hash()) {
+                    Some(tx) => replies.push(Message::Tx(tx.clone())),
+                    None => missing.push(*inv_hash),
+                },
+                ObjectKind::Error | ObjectKind::FilteredBlock => missing.push(*inv_hash),
+            }
+        }
+
+        if !missing.is_empty() {
+            replies.push(Message::NotFound(Inv::new(missing)));
+        }
+
+        replies
+    }
+
+    /// Returns the inventory of the transactions in the memory pool.
+    pub fn mempool_inv(&self) -> Inv {
+        let inventory = self
+            .mempool
+            .iter()
+            .map(|hash| self.txs[hash].inv_hash())
+            .collect();
+
+        Inv::new(inventory)
+    }
+
+    /// Returns the height of the first locator hash known to the chain, the genesis block is used
+    /// if none of them are known.
+    fn fork_height(&self, locator: &LocatorHashes) -> usize {
+        locator
+            .block_locator_hashes
+            .iter()
+            .find_map(|hash| self.heights.get(hash).copied())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::payload::inv::InvHash;
+
+    fn hash(chain: &Chain, height: usize) -> Hash {
+        chain.blocks()[height].double_sha256().unwrap()
+    }
+
+    fn headers(chain: &Chain, range: std::ops::Range<usize>) -> Message {
+        Message::Headers(Headers::new(
+            chain.blocks()[range]
+                .iter()
+                .map(|block| block.header.clone())
+                .collect(),
+        ))
+    }
+
+    fn block_inv(chain: &Chain, range: std::ops::Range<usize>) -> Message {
+        Message::Inv(Inv::new(
+            chain.blocks()[range]
+                .iter()
+                .map(|block| block.inv_hash())
+                .collect(),
+        ))
+    }
+
+    #[test]
+    #[ignore]
+    fn get_headers_latches_onto_first_known_hash() {
+        let chain = Chain::testnet(11);
+        let query = Message::GetHeaders(LocatorHashes::new(
+            vec![Hash::new([19; 32]), hash(&chain, 3), hash(&chain, 1)],
+            Hash::zeroed(),
+        ));
+
+        assert_eq!(chain.reply(&query), vec![headers(&chain, 4..11)]);
+    }
+
+    #[test]
+    #[ignore]
+    fn get_headers_includes_stop_hash() {
+        let chain = Chain::testnet(11);
+        let query = Message::GetHeaders(LocatorHashes::new(vec![hash(&chain, 3)], hash(&chain, 9)));
+        assert_eq!(chain.reply(&query), vec![headers(&chain, 4..10)]);
+
+        let query = Message::GetHeaders(LocatorHashes::new(vec![hash(&chain, 4)], hash(&chain, 4)));
+        assert_eq!(chain.reply(&query), vec![headers(&chain, 0..0)]);
+    }
+
+    #[test]
+    #[ignore]
+    fn get_blocks_excludes_stop_hash() {
+        let chain = Chain::testnet(11);
+        let query = Message::GetBlocks(LocatorHashes::new(vec![hash(&chain, 3)], hash(&chain, 9)));
+        assert_eq!(chain.reply(&query), vec![block_inv(&chain, 4..9)]);
+
+        // The tip has no children, so the query is ignored.
+        let query = Message::GetBlocks(LocatorHashes::new(vec![hash(&chain, 10)], Hash::zeroed()));
+        assert!(chain.reply(&query).is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn get_data_serves_known_objects_then_not_found() {
+        let chain = Chain::testnet(11);
+        let tx = chain.blocks()[2].txs[0].clone();
+        let missing = InvHash::new(ObjectKind::Block, Hash::new([17; 32]));
+
+        let query = Message::GetData(Inv::new(vec![
+            chain.blocks()[7].inv_hash(),
+            missing,
+            tx.inv_hash(),
+        ]));
+
+        assert_eq!(
+            chain.reply(&query),
+            vec![
+                Message::Block(Box::new(chain.blocks()[7].clone())),
+                Message::Tx(tx),
+                Message::NotFound(Inv::new(vec![missing])),
+            ]
+        );
+        assert!(chain.has_served_block(7));
+        assert!(!chain.has_served_block(2));
+    }
+
+    #[test]
+    #[ignore]
+    fn mempool_lists_transactions() {
+        let tx = Block::testnet_1().txs[0].clone();
+        let chain = Chain::testnet(1).with_mempool(vec![tx.clone()]);
+
+        assert_eq!(
+            chain.reply(&Message::MemPool),
+            vec![Message::Inv(Inv::new(vec![tx.inv_hash()]))]
+        );
+        assert_eq!(
+            chain.reply(&Message::GetData(Inv::new(vec![tx.inv_hash()]))),
+            vec![Message::Tx(tx)]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Traffic statistics for the connections of a synthetic node.
+
+use metrics::{Key, Label, Unit};
+
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Name of the counter metric recording the number of messages.
+pub const METRIC_MESSAGES: &str = "synthetic_node_messages";
+/// Name of the counter metric recording the number of bytes (headers included).
+pub const METRIC_BYTES: &str = "synthetic_node_bytes";
+
+/// The direction of a connection's traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Traffic received from the peer.
+    Inbound,
+    /// Traffic sent to the peer.
+    Outbound,
+}
+
+impl Direction {
+    /// Returns the value used for the `direction` metrics label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Inbound => "in",
+            Self::Outbound => "out",
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The number of messages and bytes sent with a single command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    pub messages: u64,
+    /// Bytes including the message headers.
+    pub bytes: u64,
+}
+
+/// The traffic of a connection in a single direction.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficStats {
+    /// Statistics indexed by command (without its padding, e.g. `"ping"`).
+    pub commands: HashMap<String, CommandStats>,
+    pub first_message: Option<Instant>,
+    pub last_message: Option<Instant>,
+}
+
+impl TrafficStats {
+    /// Returns the statistics of the command, which are zero if it wasn't seen.
+    pub fn command(&self, command: &str) -> CommandStats {
+        self.commands.get(command).copied().unwrap_or_default()
+    }
+
+    /// Returns the total number of messages.
+    pub fn messages(&self) -> u64 {
+        self.commands.values().map(|stats| stats.messages).sum()
+    }
+
+    /// Returns the total number of bytes.
+    pub fn bytes(&self) -> u64 {
+        self.commands.values().map(|stats| stats.bytes).sum()
+    }
+
+    /// Returns the time in between the first and last message.
+    pub fn duration(&self) -> Duration {
+        match (self.first_message, self.last_message) {
+            (Some(first), Some(last)) => last.duration_since(first),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns the average bandwidth in bytes per second in between the first and last message,
+    /// `None` if they coincide.
+    pub fn bandwidth(&self) -> Option<f64> {
+        let duration = self.duration();
+        (!duration.is_zero()).then(|| self.bytes() as f64 / duration.as_secs_f64())
+    }
+
+    /// Records a message, returns `true` if it's the first one with this command.
+    fn record(&mut self, command: &str, bytes: usize) -> bool {
+        let now = Instant::now();
+        self.first_message.get_or_insert(now);
+        self.last_message = Some(now);
+
+        let is_new = !self.commands.contains_key(command);
+        let stats = self.commands.entry(command.to_owned()).or_default();
+        stats.messages += 1;
+        stats.bytes += bytes as u64;
+
+        is_new
+    }
+}
+
+/// The traffic of a connection, counted per command and direction.
+///
+/// Every frame is counted, including the handshake messages and the frames which couldn't be
+/// decoded.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    pub inbound: TrafficStats,
+    pub outbound: TrafficStats,
+}
+
+impl ConnectionStats {
+    /// Returns the traffic in the given direction.
+    pub fn direction(&self, direction: Direction) -> &TrafficStats {
+        match direction {
+            Direction::Inbound => &self.inbound,
+            Direction::Outbound => &self.outbound,
+        }
+    }
+
+    /// Records a message of `bytes` (header included), returns `true` if it's the first one with
+    /// this command in this direction.
+    pub(super) fn record(&mut self, direction: Direction, command: &str, bytes: usize) -> bool {
+        match direction {
+            Direction::Inbound => self.inbound.record(command, bytes),
+            Direction::Outbound => self.outbound.record(command, bytes),
+        }
+    }
+}
+
+/// Returns the command as a string, without its padding.
+pub(super) fn command_str(command: &[u8]) -> String {
+    String::from_utf8_lossy(command)
+        .trim_end_matches('\0')
+        .to_owned()
+}
+
+/// Pushes a message to the [`METRIC_MESSAGES`] and [`METRIC_BYTES`] counters, labelled with
+/// `local` (the synthetic node's listening address), `peer`, `command` and `direction`. The
+/// counters are registered on the first message of the label set (see `register`), as the
+/// recorder ignores unregistered metrics.
+///
+/// The `local` label keeps the label sets of several synthetic nodes connected to the same peer
+/// apart, as registering a counter again would reset it.
+pub(super) fn record_metrics(
+    local: SocketAddr,
+    peer: SocketAddr,
+    direction: Direction,
+    command: &str,
+    bytes: usize,
+    register: bool,
+) {
+    // Without a recorder there is nothing to push to.
+    let recorder = match metrics::try_recorder() {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    let labels = vec![
+        Label::new("local", local.to_string()),
+        Label::new("peer", peer.to_string()),
+        Label::new("command", command.to_owned()),
+        Label::new("direction", direction.as_str()),
+    ];
+    let messages_key = Key::from_parts(METRIC_MESSAGES, labels.clone());
+    let bytes_key = Key::from_parts(METRIC_BYTES, labels);
+
+    if register {
+        recorder.register_counter(&messages_key, None, None);
+        recorder.register_counter(&bytes_key, Some(Unit::Bytes), None);
+    }
+
+    recorder.increment_counter(&messages_key, 1);
+    recorder.increment_counter(&bytes_key, bytes as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn records_per_command_and_direction() {
+        let mut stats = ConnectionStats::default();
+
+        assert!(stats.record(Direction::Outbound, "ping", 32));
+        assert!(!stats.record(Direction::Outbound, "ping", 32));
+        assert!(stats.record(Direction::Outbound, "getaddr", 24));
+        assert!(stats.record(Direction::Inbound, "ping", 32));
+
+        assert_eq!(
+            stats.outbound.command("ping"),
+            CommandStats {
+                messages: 2,
+                bytes: 64
+            }
+        );
+        assert_eq!(stats.outbound.messages(), 3);
+        assert_eq!(stats.outbound.bytes(), 88);
+        assert_eq!(stats.inbound.messages(), 1);
+        assert_eq!(stats.inbound.command("pong"), CommandStats::default());
+        assert!(stats.outbound.first_message <= stats.outbound.last_message);
+    }
+
+    #[test]
+    #[ignore]
+    fn command_padding_is_trimmed() {
+        assert_eq!(command_str(b"getaddr\0\0\0\0\0"), "getaddr");
+    }
+}
@@ -0,0 +1,204 @@
+//! The bounded inbound queue of a synthetic node.
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+/// What happens to an inbound message when the queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Wait for space in the queue, which holds back reading from the connection.
+    #[default]
+    Block,
+    /// Drop the oldest queued message to make space.
+    DropOldest,
+    /// Drop the incoming message.
+    DropNewest,
+    /// Never queue anything, only count the messages as dropped. For tests which don't care
+    /// about the inbound traffic.
+    CountAndDrop,
+}
+
+/// A bounded multi-producer, single-consumer queue applying a [`QueuePolicy`] when full.
+///
+/// Once closed, pushed items are dropped quietly (without being counted).
+#[derive(Debug)]
+pub(super) struct InboundQueue<T> {
+    items: Mutex<VecDeque<T>>,
+    capacity: usize,
+    policy: QueuePolicy,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    /// Notified when an item is pushed.
+    pushed: Notify,
+    /// Notified when an item is popped, or the queue is closed.
+    popped: Notify,
+}
+
+impl<T> InboundQueue<T> {
+    pub(super) fn new(capacity: usize, policy: QueuePolicy) -> Self {
+        assert!(capacity > 0, "inbound capacity must be non-zero");
+
+        Self {
+            items: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            policy,
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            pushed: Notify::new(),
+            popped: Notify::new(),
+        }
+    }
+
+    /// Pushes the item according to the policy, only waits if the policy is
+    /// [`QueuePolicy::Block`].
+    pub(super) async fn push(&self, item: T) {
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                // Pass the wake up on to the next blocked push.
+                self.popped.notify_one();
+                return;
+            }
+
+            {
+                let mut items = self.items.lock();
+
+                if self.policy == QueuePolicy::CountAndDrop {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+
+                if items.len() < self.capacity {
+                    items.push_back(item);
+                    self.pushed.notify_one();
+                    return;
+                }
+
+                match self.policy {
+                    QueuePolicy::DropOldest => {
+                        items.pop_front();
+                        items.push_back(item);
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        self.pushed.notify_one();
+                        return;
+                    }
+                    QueuePolicy::DropNewest => {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    QueuePolicy::Block | QueuePolicy::CountAndDrop => {}
+                }
+            }
+
+            // The permit stored by `notify_one` covers a pop in between the check and the wait.
+            self.popped.notified().await;
+        }
+    }
+
+    /// Pops the oldest item, waiting for one if the queue is empty.
+    pub(super) async fn pop(&self) -> T {
+        loop {
+            if let Some(item) = self.try_pop() {
+                return item;
+            }
+
+            self.pushed.notified().await;
+        }
+    }
+
+    /// Pops the oldest item, if any.
+    pub(super) fn try_pop(&self) -> Option<T> {
+        let item = self.items.lock().pop_front();
+        if item.is_some() {
+            self.popped.notify_one();
+        }
+
+        item
+    }
+
+    /// Returns the number of queued items.
+    pub(super) fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    /// Returns the number of items dropped by the policy.
+    pub(super) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Closes the queue, releasing any blocked pushes.
+    pub(super) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        // The stored permit also covers a push about to wait.
+        self.popped.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::TIMEOUT;
+
+    use std::sync::Arc;
+    use tokio::time::timeout;
+
+    async fn filled(policy: QueuePolicy) -> InboundQueue<usize> {
+        let queue = InboundQueue::new(2, policy);
+        for i in 0..4 {
+            queue.push(i).await;
+        }
+
+        queue
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn drop_policies() {
+        let queue = filled(QueuePolicy::DropOldest).await;
+        assert_eq!(
+            (queue.try_pop(), queue.try_pop(), queue.dropped()),
+            (Some(2), Some(3), 2)
+        );
+
+        let queue = filled(QueuePolicy::DropNewest).await;
+        assert_eq!(
+            (queue.try_pop(), queue.try_pop(), queue.dropped()),
+            (Some(0), Some(1), 2)
+        );
+
+        let queue = filled(QueuePolicy::CountAndDrop).await;
+        assert_eq!((queue.len(), queue.dropped()), (0, 4));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn block_waits_for_space_until_closed() {
+        let queue = Arc::new(InboundQueue::new(1, QueuePolicy::Block));
+        queue.push(0).await;
+
+        let pusher = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.push(1).await })
+        };
+
+        assert_eq!(queue.pop().await, 0);
+        timeout(TIMEOUT, pusher).await.unwrap().unwrap();
+        assert_eq!(queue.pop().await, 1);
+
+        // A closed queue quietly drops what's pushed, including blocked pushes.
+        queue.push(2).await;
+        let pusher = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.push(3).await })
+        };
+        tokio::task::yield_now().await;
+        queue.close();
+        timeout(TIMEOUT, pusher).await.unwrap().unwrap();
+        queue.push(4).await;
+        assert_eq!((queue.len(), queue.dropped()), (1, 0));
+    }
+}
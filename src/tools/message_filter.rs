@@ -1,12 +1,20 @@
 //! Message filtering types and utilities.
 
-use crate::protocol::{
-    message::Message,
-    payload::{block::Headers, Addr},
+use crate::{
+    protocol::{
+        message::Message,
+        payload::{block::Headers, Addr, Inv},
+    },
+    tools::chain::Chain,
 };
 
+use std::{fmt, net::SocketAddr, sync::Arc};
+
+/// A closure which maps a request (and the address of its sender) to a list of replies.
+pub type Responder = Arc<dyn Fn(SocketAddr, &Message) -> Vec<Message> + Send + Sync>;
+
 /// Controls the filter response of [`MessageFilter`] to messages it receives.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Clone)]
 pub enum Filter {
     /// Do not filter message
     Disabled,
@@ -14,76 +22,209 @@ pub enum Filter {
     Enabled,
     /// Filter message and reply with a default response
     AutoReply,
+    /// Filter message and reply with the messages returned by the [`Responder`], in order
+    Custom(Responder),
+}
+
+impl Filter {
+    /// Constructs a [`Filter::Custom`] from the given closure.
+    pub fn custom<F>(responder: F) -> Self
+    where
+        F: Fn(SocketAddr, &Message) -> Vec<Message> + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(responder))
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => f.write_str("Disabled"),
+            Self::Enabled => f.write_str("Enabled"),
+            Self::AutoReply => f.write_str("AutoReply"),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl PartialEq for Filter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Disabled, Self::Disabled)
+            | (Self::Enabled, Self::Enabled)
+            | (Self::AutoReply, Self::AutoReply) => true,
+            // Closures can't be compared, two custom filters are only equal if they share one.
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
 }
 
-/// A message filter that can map requests to default responses.
+/// A message filter that can map requests to default or custom responses.
 ///
 /// This can be used to wait for a message event that you actually care about,
 /// while skipping over spurious requests e.g. [`Ping`].
 ///
-/// Currently supports filters for the following message types:
-/// - [`Ping`]
-/// - [`GetHeaders`]
-/// - [`GetAddr`]
-/// - [`GetData`]
+/// A [`Filter`] can be set for every message type. [`Filter::AutoReply`] has a default response
+/// for the following requests, other message types are filtered without a reply:
+/// - [`Ping`] (empty [`Pong`])
+/// - [`GetAddr`] (empty [`Addr`])
+/// - [`GetHeaders`] (empty [`Headers`])
+/// - [`GetBlocks`] (empty [`Inv`])
+/// - [`GetData`] ([`NotFound`] for the whole inventory)
+/// - [`MemPool`] (empty [`Inv`])
 ///
 /// [`Ping`]: Message::Ping
-/// [`GetHeaders`]: Message::GetHeaders
+/// [`Pong`]: Message::Pong
 /// [`GetAddr`]: Message::GetAddr
+/// [`Addr`]: Message::Addr
+/// [`GetHeaders`]: Message::GetHeaders
+/// [`Headers`]: Message::Headers
+/// [`GetBlocks`]: Message::GetBlocks
+/// [`Inv`]: Message::Inv
 /// [`GetData`]: Message::GetData
+/// [`NotFound`]: Message::NotFound
+/// [`MemPool`]: Message::MemPool
 #[derive(Debug, Clone)]
 pub struct MessageFilter {
+    version: Filter,
+    verack: Filter,
     ping: Filter,
-    getheaders: Filter,
+    pong: Filter,
     getaddr: Filter,
+    addr: Filter,
+    getheaders: Filter,
+    headers: Filter,
+    getblocks: Filter,
+    block: Filter,
     getdata: Filter,
-    // todo: inv
-    // todo: getblocks
-    // todo: mempool
+    inv: Filter,
+    notfound: Filter,
+    mempool: Filter,
+    tx: Filter,
+    reject: Filter,
+    filterload: Filter,
+    filteradd: Filter,
+    filterclear: Filter,
 }
 
 impl MessageFilter {
     /// Constructs a `MessageFilter` which will filter no messages.
     pub fn with_all_disabled() -> Self {
-        use Filter::Disabled;
-
-        Self {
-            ping: Disabled,
-            getheaders: Disabled,
-            getaddr: Disabled,
-            getdata: Disabled,
-        }
+        Self::with_all(Filter::Disabled)
     }
 
-    /// Constructs a `MessageFilter` which will filter all supported message types.
+    /// Constructs a `MessageFilter` which will filter [`Ping`], [`GetHeaders`], [`GetAddr`] and
+    /// [`GetData`] messages, other message types are not filtered.
+    ///
+    /// [`Ping`]: Message::Ping
+    /// [`GetHeaders`]: Message::GetHeaders
+    /// [`GetAddr`]: Message::GetAddr
+    /// [`GetData`]: Message::GetData
     pub fn with_all_enabled() -> Self {
-        use Filter::Enabled;
+        Self::with_basic_requests(Filter::Enabled)
+    }
 
-        Self {
-            ping: Enabled,
-            getheaders: Enabled,
-            getaddr: Enabled,
-            getdata: Enabled,
-        }
+    /// Constructs a `MessageFilter` which will filter every message type.
+    pub fn with_all_types_enabled() -> Self {
+        Self::with_all(Filter::Enabled)
     }
 
-    /// Constructs a `MessageFilter` which will filter and reply to all supported message types.
+    /// Constructs a `MessageFilter` which will filter and reply to [`Ping`], [`GetHeaders`],
+    /// [`GetAddr`] and [`GetData`] messages with a default response (see [`MessageFilter`]),
+    /// other message types are not filtered.
+    ///
+    /// [`Ping`]: Message::Ping
+    /// [`GetHeaders`]: Message::GetHeaders
+    /// [`GetAddr`]: Message::GetAddr
+    /// [`GetData`]: Message::GetData
     pub fn with_all_auto_reply() -> Self {
-        use Filter::AutoReply;
+        Self::with_basic_requests(Filter::AutoReply)
+    }
 
+    /// Constructs a `MessageFilter` which will filter and reply to every request with a default
+    /// response (see [`MessageFilter`]), i.e. [`with_all_auto_reply`] as well as [`GetBlocks`]
+    /// and [`MemPool`] messages. Other message types are not filtered.
+    ///
+    /// [`with_all_auto_reply`]: MessageFilter::with_all_auto_reply
+    /// [`GetBlocks`]: Message::GetBlocks
+    /// [`MemPool`]: Message::MemPool
+    pub fn with_all_requests_auto_reply() -> Self {
+        Self::with_all_auto_reply()
+            .with_getblocks_filter(Filter::AutoReply)
+            .with_mempool_filter(Filter::AutoReply)
+    }
+
+    /// Sets the filter for [`Ping`], [`GetHeaders`], [`GetAddr`] and [`GetData`] messages, other
+    /// message types are not filtered.
+    ///
+    /// [`Ping`]: Message::Ping
+    /// [`GetHeaders`]: Message::GetHeaders
+    /// [`GetAddr`]: Message::GetAddr
+    /// [`GetData`]: Message::GetData
+    fn with_basic_requests(filter: Filter) -> Self {
         Self {
-            ping: AutoReply,
-            getheaders: AutoReply,
-            getaddr: AutoReply,
-            getdata: AutoReply,
+            ping: filter.clone(),
+            getheaders: filter.clone(),
+            getaddr: filter.clone(),
+            getdata: filter,
+            ..Self::with_all_disabled()
         }
     }
 
-    /// Sets the [`Filter`] response for [`GetHeaders`] messages.
+    fn with_all(filter: Filter) -> Self {
+        Self {
+            version: filter.clone(),
+            verack: filter.clone(),
+            ping: filter.clone(),
+            pong: filter.clone(),
+            getaddr: filter.clone(),
+            addr: filter.clone(),
+            getheaders: filter.clone(),
+            headers: filter.clone(),
+            getblocks: filter.clone(),
+            block: filter.clone(),
+            getdata: filter.clone(),
+            inv: filter.clone(),
+            notfound: filter.clone(),
+            mempool: filter.clone(),
+            tx: filter.clone(),
+            reject: filter.clone(),
+            filterload: filter.clone(),
+            filteradd: filter.clone(),
+            filterclear: filter,
+        }
+    }
+
+    /// Sets the [`Filter`] response for [`Version`] messages.
     ///
-    /// [`GetHeaders`]: Message::GetHeaders
-    pub fn with_getheaders_filter(mut self, filter: Filter) -> Self {
-        self.getheaders = filter;
+    /// [`Version`]: Message::Version
+    pub fn with_version_filter(mut self, filter: Filter) -> Self {
+        self.version = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Verack`] messages.
+    ///
+    /// [`Verack`]: Message::Verack
+    pub fn with_verack_filter(mut self, filter: Filter) -> Self {
+        self.verack = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Ping`] messages.
+    ///
+    /// [`Ping`]: Message::Ping
+    pub fn with_ping_filter(mut self, filter: Filter) -> Self {
+        self.ping = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Pong`] messages.
+    ///
+    /// [`Pong`]: Message::Pong
+    pub fn with_pong_filter(mut self, filter: Filter) -> Self {
+        self.pong = filter;
         self
     }
 
@@ -95,6 +236,46 @@ impl MessageFilter {
         self
     }
 
+    /// Sets the [`Filter`] response for [`Addr`] messages.
+    ///
+    /// [`Addr`]: Message::Addr
+    pub fn with_addr_filter(mut self, filter: Filter) -> Self {
+        self.addr = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`GetHeaders`] messages.
+    ///
+    /// [`GetHeaders`]: Message::GetHeaders
+    pub fn with_getheaders_filter(mut self, filter: Filter) -> Self {
+        self.getheaders = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Headers`] messages.
+    ///
+    /// [`Headers`]: Message::Headers
+    pub fn with_headers_filter(mut self, filter: Filter) -> Self {
+        self.headers = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`GetBlocks`] messages.
+    ///
+    /// [`GetBlocks`]: Message::GetBlocks
+    pub fn with_getblocks_filter(mut self, filter: Filter) -> Self {
+        self.getblocks = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Block`] messages.
+    ///
+    /// [`Block`]: Message::Block
+    pub fn with_block_filter(mut self, filter: Filter) -> Self {
+        self.block = filter;
+        self
+    }
+
     /// Sets the [`Filter`] response for [`GetData`] messages.
     ///
     /// [`GetData`]: Message::GetData
@@ -103,33 +284,232 @@ impl MessageFilter {
         self
     }
 
-    /// Sets the [`Filter`] response for [`Ping`] messages.
+    /// Sets the [`Filter`] response for [`Inv`] messages.
     ///
-    /// [`Ping`]: Message::Ping
-    pub fn with_ping_filter(mut self, filter: Filter) -> Self {
-        self.ping = filter;
+    /// [`Inv`]: Message::Inv
+    pub fn with_inv_filter(mut self, filter: Filter) -> Self {
+        self.inv = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`NotFound`] messages.
+    ///
+    /// [`NotFound`]: Message::NotFound
+    pub fn with_notfound_filter(mut self, filter: Filter) -> Self {
+        self.notfound = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`MemPool`] messages.
+    ///
+    /// [`MemPool`]: Message::MemPool
+    pub fn with_mempool_filter(mut self, filter: Filter) -> Self {
+        self.mempool = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Tx`] messages.
+    ///
+    /// [`Tx`]: Message::Tx
+    pub fn with_tx_filter(mut self, filter: Filter) -> Self {
+        self.tx = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`Reject`] messages.
+    ///
+    /// [`Reject`]: Message::Reject
+    pub fn with_reject_filter(mut self, filter: Filter) -> Self {
+        self.reject = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`FilterLoad`] messages.
+    ///
+    /// [`FilterLoad`]: Message::FilterLoad
+    pub fn with_filterload_filter(mut self, filter: Filter) -> Self {
+        self.filterload = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`FilterAdd`] messages.
+    ///
+    /// [`FilterAdd`]: Message::FilterAdd
+    pub fn with_filteradd_filter(mut self, filter: Filter) -> Self {
+        self.filteradd = filter;
+        self
+    }
+
+    /// Sets the [`Filter`] response for [`FilterClear`] messages.
+    ///
+    /// [`FilterClear`]: Message::FilterClear
+    pub fn with_filterclear_filter(mut self, filter: Filter) -> Self {
+        self.filterclear = filter;
         self
     }
 
+    /// Sets a [`Filter::Custom`] which replies from the [`Chain`] for [`GetHeaders`],
+    /// [`GetBlocks`], [`GetData`] and [`MemPool`] messages.
+    ///
+    /// [`GetHeaders`]: Message::GetHeaders
+    /// [`GetBlocks`]: Message::GetBlocks
+    /// [`GetData`]: Message::GetData
+    /// [`MemPool`]: Message::MemPool
+    pub fn with_chain_filters(self, chain: Arc<Chain>) -> Self {
+        let responder = Filter::custom(move |_, message| chain.reply(message));
+
+        self.with_getheaders_filter(responder.clone())
+            .with_getblocks_filter(responder.clone())
+            .with_getdata_filter(responder.clone())
+            .with_mempool_filter(responder)
+    }
+
     /// Returns the set [`Filter`] for the message type.
-    pub fn message_filter_type(&self, message: &Message) -> Filter {
+    pub fn message_filter_type(&self, message: &Message) -> &Filter {
         match message {
-            Message::Ping(_) => self.ping,
-            Message::GetAddr => self.getaddr,
-            Message::GetHeaders(_) => self.getheaders,
-            Message::GetData(_) => self.getdata,
-            _ => Filter::Disabled,
+            Message::Version(_) => &self.version,
+            Message::Verack => &self.verack,
+            Message::Ping(_) => &self.ping,
+            Message::Pong(_) => &self.pong,
+            Message::GetAddr => &self.getaddr,
+            Message::Addr(_) => &self.addr,
+            Message::GetHeaders(_) => &self.getheaders,
+            Message::Headers(_) => &self.headers,
+            Message::GetBlocks(_) => &self.getblocks,
+            Message::Block(_) => &self.block,
+            Message::GetData(_) => &self.getdata,
+            Message::Inv(_) => &self.inv,
+            Message::NotFound(_) => &self.notfound,
+            Message::MemPool => &self.mempool,
+            Message::Tx(_) => &self.tx,
+            Message::Reject(_) => &self.reject,
+            Message::FilterLoad(_) => &self.filterload,
+            Message::FilterAdd(_) => &self.filteradd,
+            Message::FilterClear => &self.filterclear,
         }
     }
 
-    /// Returns the appropriate reply for the message.
-    pub fn reply_message(&self, message: &Message) -> Message {
-        match message {
+    /// Returns the replies to the message from `source`, as determined by its [`Filter`].
+    ///
+    /// Messages set to [`Filter::Disabled`] or [`Filter::Enabled`] get no replies.
+    pub fn reply_messages(&self, source: SocketAddr, message: &Message) -> Vec<Message> {
+        match self.message_filter_type(message) {
+            Filter::AutoReply => self.reply_message(message).into_iter().collect(),
+            Filter::Custom(responder) => responder(source, message),
+            Filter::Disabled | Filter::Enabled => Vec::new(),
+        }
+    }
+
+    /// Returns the default reply for the message regardless of its [`Filter`] (see
+    /// [`MessageFilter`]), `None` if it has none.
+    pub fn reply_message(&self, message: &Message) -> Option<Message> {
+        let reply = match message {
             Message::Ping(nonce) => Message::Pong(*nonce),
             Message::GetAddr => Message::Addr(Addr::empty()),
             Message::GetHeaders(_) => Message::Headers(Headers::empty()),
+            Message::GetBlocks(_) => Message::Inv(Inv::empty()),
             Message::GetData(inv) => Message::NotFound(inv.clone()),
-            _ => unimplemented!(),
+            Message::MemPool => Message::Inv(Inv::empty()),
+            _ => return None,
+        };
+
+        Some(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::payload::Nonce;
+
+    fn source() -> SocketAddr {
+        "127.0.0.1:8080".parse().unwrap()
+    }
+
+    #[test]
+    #[ignore]
+    fn auto_reply_only_filters_requests() {
+        let filter = MessageFilter::with_all_requests_auto_reply();
+
+        assert_eq!(
+            filter.message_filter_type(&Message::MemPool),
+            &Filter::AutoReply
+        );
+        assert_eq!(
+            filter.message_filter_type(&Message::Inv(Inv::empty())),
+            &Filter::Disabled
+        );
+
+        let nonce = Nonce::default();
+        assert_eq!(
+            filter.reply_messages(source(), &Message::Ping(nonce)),
+            vec![Message::Pong(nonce)]
+        );
+        assert_eq!(
+            filter.reply_messages(source(), &Message::MemPool),
+            vec![Message::Inv(Inv::empty())]
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn original_constructors_only_filter_basic_requests() {
+        let auto_reply = MessageFilter::with_all_auto_reply();
+        assert_eq!(
+            auto_reply.message_filter_type(&Message::GetAddr),
+            &Filter::AutoReply
+        );
+        for message in &[Message::MemPool, Message::Verack] {
+            assert_eq!(auto_reply.message_filter_type(message), &Filter::Disabled);
         }
+
+        let enabled = MessageFilter::with_all_enabled();
+        assert_eq!(
+            enabled.message_filter_type(&Message::GetAddr),
+            &Filter::Enabled
+        );
+        assert_eq!(
+            enabled.message_filter_type(&Message::Verack),
+            &Filter::Disabled
+        );
+        assert_eq!(
+            MessageFilter::with_all_types_enabled().message_filter_type(&Message::Verack),
+            &Filter::Enabled
+        );
+
+        assert_eq!(
+            auto_reply.reply_message(&Message::GetAddr),
+            Some(Message::Addr(Addr::empty()))
+        );
+        assert_eq!(auto_reply.reply_message(&Message::Verack), None);
+    }
+
+    #[test]
+    #[ignore]
+    fn auto_reply_without_default_response_is_silent() {
+        let filter = MessageFilter::with_all_disabled().with_verack_filter(Filter::AutoReply);
+
+        assert!(filter.reply_messages(source(), &Message::Verack).is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn custom_filter_replies_in_sequence() {
+        let filter = MessageFilter::with_all_disabled().with_mempool_filter(Filter::custom(
+            |source, message| {
+                assert_eq!(source, "127.0.0.1:8080".parse().unwrap());
+                assert_eq!(message, &Message::MemPool);
+
+                vec![Message::Inv(Inv::empty()), Message::GetAddr]
+            },
+        ));
+
+        assert_eq!(
+            filter.reply_messages(source(), &Message::MemPool),
+            vec![Message::Inv(Inv::empty()), Message::GetAddr]
+        );
+        assert!(filter
+            .reply_messages(source(), &Message::GetAddr)
+            .is_empty());
     }
 }
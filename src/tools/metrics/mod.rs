@@ -1,4 +1,5 @@
 //! Metrics types and utilities.
 
 pub mod recorder;
+pub mod resources;
 pub mod tables;
@@ -114,6 +114,7 @@ impl metrics::Recorder for SimpleRecorder {
 
 lazy_static::lazy_static! {
     static ref SIMPLE_RECORDER: SimpleRecorder = SimpleRecorder::default();
+    static ref EXCLUSIVE_USE: tokio::sync::Mutex<()> = Default::default();
 }
 
 /// Enables the [`SimpleRecorder`] as the
@@ -130,6 +131,16 @@ pub fn enable_simple_recorder() -> Result<(), SetRecorderError> {
     Ok(())
 }
 
+/// Enables the [`SimpleRecorder`] (see [`enable_simple_recorder`]) and takes exclusive use of it
+/// until the returned guard is dropped.
+///
+/// The recorder is shared by the whole process, tests which [`clear`] it or read back their
+/// metrics hold this guard so they don't corrupt each other's results when run in parallel.
+pub async fn enable_exclusive() -> tokio::sync::MutexGuard<'static, ()> {
+    let _ = enable_simple_recorder();
+    EXCLUSIVE_USE.lock().await
+}
+
 /// Map of all counters recorded.
 pub fn counters() -> Arc<Mutex<HashMap<Key, Counter>>> {
     SIMPLE_RECORDER.counters.clone()
@@ -0,0 +1,241 @@
+//! Resource usage sampling of a process (e.g. the node under test), read from `/proc`.
+
+use metrics::{GaugeValue, Key, Unit};
+use parking_lot::Mutex;
+use tokio::{task::JoinHandle, time::Instant};
+
+use std::{collections::HashMap, fs, io, sync::Arc, time::Duration};
+
+/// Name of the gauge metric recording the resident set size, in bytes.
+pub const METRIC_RSS: &str = "node_rss";
+/// Name of the gauge metric recording the CPU usage since the previous sample, in percent of a
+/// single core.
+pub const METRIC_CPU: &str = "node_cpu";
+/// Name of the gauge metric recording the number of threads.
+pub const METRIC_THREADS: &str = "node_threads";
+/// Name of the gauge metric recording the number of open file descriptors.
+pub const METRIC_FDS: &str = "node_fds";
+/// Name of the gauge metric recording the number of open sockets.
+pub const METRIC_SOCKETS: &str = "node_sockets";
+
+/// The resource usage of a process and its descendants at a point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceSample {
+    /// The time since sampling started.
+    pub elapsed: Duration,
+    /// The resident set size, in bytes.
+    pub rss: u64,
+    /// The total CPU time (user and system).
+    pub cpu_time: Duration,
+    /// The CPU usage since the previous sample, in percent of a single core.
+    pub cpu: f64,
+    pub threads: u64,
+    /// The number of open file descriptors, sockets included.
+    pub fds: u64,
+    pub sockets: u64,
+}
+
+impl ResourceSample {
+    /// Reads the current resource usage of the process and its descendants (e.g. when the node
+    /// is started with `cargo run`). The `elapsed` and `cpu` fields are left to the caller.
+    pub fn read(pid: u32) -> io::Result<Self> {
+        // Safe, these only query system constants.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+
+        let mut sample = Self::default();
+        let mut ticks = 0;
+        for (i, pid) in with_descendants(pid)?.into_iter().enumerate() {
+            let stat = match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                Ok(stat) => stat,
+                // A descendant may have exited in the meantime.
+                Err(_) if i > 0 => continue,
+                Err(e) => return Err(e),
+            };
+
+            // The fields following the (parenthesised) command name, starting with the state
+            // (the 3rd field), see `man 5 proc`.
+            let fields: Vec<u64> = stat
+                .rsplit_once(')')
+                .map(|(_, fields)| fields)
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|field| field.parse().unwrap_or(0))
+                .collect();
+            if fields.len() < 22 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected /proc/{}/stat format", pid),
+                ));
+            }
+
+            // utime and stime are the 14th and 15th fields, num_threads the 20th, rss the 24th.
+            ticks += fields[11] + fields[12];
+            sample.threads += fields[17];
+            sample.rss += fields[21] * page_size;
+
+            if let Ok(fds) = fs::read_dir(format!("/proc/{}/fd", pid)) {
+                for fd in fds.flatten() {
+                    sample.fds += 1;
+                    if let Ok(target) = fs::read_link(fd.path()) {
+                        if target.to_string_lossy().starts_with("socket:[") {
+                            sample.sockets += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        sample.cpu_time = Duration::from_secs_f64(ticks as f64 / ticks_per_sec as f64);
+
+        Ok(sample)
+    }
+
+    /// Pushes the sample to the gauges (if a metrics recorder is installed).
+    fn record_metrics(&self) {
+        let recorder = match metrics::try_recorder() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        for (name, unit, value) in [
+            (METRIC_RSS, Some(Unit::Bytes), self.rss as f64),
+            (METRIC_CPU, Some(Unit::Percent), self.cpu),
+            (METRIC_THREADS, None, self.threads as f64),
+            (METRIC_FDS, None, self.fds as f64),
+            (METRIC_SOCKETS, None, self.sockets as f64),
+        ] {
+            let key = Key::from_name(name);
+            // Registered on every sample, as tests clear the recorder in between iterations.
+            recorder.register_gauge(&key, unit, None);
+            recorder.update_gauge(&key, GaugeValue::Absolute(value));
+        }
+    }
+}
+
+/// Samples the resource usage of a process at a regular interval in the background, pushing
+/// the samples to the gauge metrics (e.g. [`METRIC_RSS`]) and keeping them as a time series.
+///
+/// Sampling stops once the process exits, or when the sampler is dropped.
+pub struct ResourceSampler {
+    samples: Arc<Mutex<Vec<ResourceSample>>>,
+    handle: JoinHandle<()>,
+}
+
+impl ResourceSampler {
+    /// Starts sampling the process (and its descendants) every `interval`.
+    pub fn start(pid: u32, interval: Duration) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = {
+            let samples = samples.clone();
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let mut previous: Option<ResourceSample> = None;
+
+                loop {
+                    let mut sample = match ResourceSample::read(pid) {
+                        Ok(sample) => sample,
+                        Err(_) => return,
+                    };
+                    sample.elapsed = start.elapsed();
+                    if let Some(previous) = previous {
+                        let wall_time = sample.elapsed - previous.elapsed;
+                        let cpu_time = sample.cpu_time.saturating_sub(previous.cpu_time);
+                        sample.cpu = cpu_time.as_secs_f64() / wall_time.as_secs_f64() * 100.0;
+                    }
+
+                    sample.record_metrics();
+                    samples.lock().push(sample);
+                    previous = Some(sample);
+
+                    tokio::time::sleep(interval).await;
+                }
+            })
+        };
+
+        Self { samples, handle }
+    }
+
+    /// Returns the samples taken since the start, or the last [`ResourceSampler::reset`].
+    pub fn samples(&self) -> Vec<ResourceSample> {
+        self.samples.lock().clone()
+    }
+
+    /// Discards the samples taken so far, e.g. in between the iterations of a test.
+    pub fn reset(&self) {
+        self.samples.lock().clear();
+    }
+}
+
+impl Drop for ResourceSampler {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Returns the process and its descendants.
+pub(crate) fn with_descendants(pid: u32) -> io::Result<Vec<u32>> {
+    let mut parents: HashMap<u32, Vec<u32>> = HashMap::new();
+    for entry in fs::read_dir("/proc")?.flatten() {
+        let child = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(child) => child,
+            None => continue,
+        };
+
+        // The parent's pid is the second field after the (parenthesised) command name.
+        if let Ok(stat) = fs::read_to_string(entry.path().join("stat")) {
+            if let Some(parent) = stat
+                .rsplit_once(')')
+                .and_then(|(_, fields)| fields.split_whitespace().nth(1))
+                .and_then(|parent| parent.parse().ok())
+            {
+                parents.entry(parent).or_default().push(child);
+            }
+        }
+    }
+
+    let mut pids = vec![pid];
+    let mut i = 0;
+    while i < pids.len() {
+        if let Some(children) = parents.get(&pids[i]) {
+            pids.extend(children);
+        }
+        i += 1;
+    }
+
+    Ok(pids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::metrics::tables::ResourceStats;
+
+    #[tokio::test]
+    #[ignore]
+    async fn samples_this_process() {
+        let _listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let sampler = ResourceSampler::start(std::process::id(), Duration::from_millis(10));
+        crate::wait_until!(Duration::from_secs(1), sampler.samples().len() >= 3);
+
+        let samples = sampler.samples();
+        let last = samples.last().unwrap();
+        assert!(last.rss > 0);
+        assert!(last.threads >= 1);
+        assert!(last.sockets >= 1 && last.fds >= last.sockets);
+        assert!(samples.windows(2).all(|w| w[0].elapsed < w[1].elapsed));
+
+        let stats = ResourceStats::new(&samples);
+        assert!(stats.rss_peak >= stats.rss_average && stats.rss_average > 0.0);
+        assert!(stats.sockets_peak >= 1);
+
+        sampler.reset();
+        assert!(sampler.samples().len() <= 1);
+    }
+}
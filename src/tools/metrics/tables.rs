@@ -1,5 +1,7 @@
 //! Tables to display metrics.
 
+use crate::tools::metrics::resources::ResourceSample;
+
 use histogram::Histogram;
 use tabled::{Alignment, Modify, Style, Table, Tabled};
 use tokio::time::Duration;
@@ -78,6 +80,59 @@ impl std::fmt::Display for RequestsTable {
     }
 }
 
+/// Peak and average resource usage of a node, to be inlined in a stats table (with
+/// `#[header(inline)]`).
+#[derive(Tabled, Default, Debug, Clone)]
+pub struct ResourceStats {
+    #[header(" peak \n RSS (MiB) ")]
+    #[field(display_with = "table_float_display")]
+    pub rss_peak: f64,
+    #[header(" avg \n RSS (MiB) ")]
+    #[field(display_with = "table_float_display")]
+    pub rss_average: f64,
+    #[header(" peak \n CPU % ")]
+    #[field(display_with = "table_float_display")]
+    pub cpu_peak: f64,
+    #[header(" avg \n CPU % ")]
+    #[field(display_with = "table_float_display")]
+    pub cpu_average: f64,
+    #[header(" peak \n threads ")]
+    pub threads_peak: u64,
+    #[header(" peak \n fds ")]
+    pub fds_peak: u64,
+    #[header(" peak \n sockets ")]
+    pub sockets_peak: u64,
+    #[header(" avg \n sockets ")]
+    #[field(display_with = "table_float_display")]
+    pub sockets_average: f64,
+}
+
+impl ResourceStats {
+    /// Computes the statistics of the samples, which are all zero if there are none.
+    pub fn new(samples: &[ResourceSample]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        const MIB: f64 = 1024.0 * 1024.0;
+        let n = samples.len() as f64;
+        let average =
+            |value: fn(&ResourceSample) -> f64| samples.iter().map(value).sum::<f64>() / n;
+        let peak = |value: fn(&ResourceSample) -> u64| samples.iter().map(value).max().unwrap_or(0);
+
+        Self {
+            rss_peak: peak(|sample| sample.rss) as f64 / MIB,
+            rss_average: average(|sample| sample.rss as f64) / MIB,
+            cpu_peak: samples.iter().map(|sample| sample.cpu).fold(0.0, f64::max),
+            cpu_average: average(|sample| sample.cpu),
+            threads_peak: peak(|sample| sample.threads),
+            fds_peak: peak(|sample| sample.fds),
+            sockets_peak: peak(|sample| sample.sockets),
+            sockets_average: average(|sample| sample.sockets as f64),
+        }
+    }
+}
+
 /// Formats `f64` with 2 decimal points.
 pub fn table_float_display(x: &f64) -> String {
     format!("{0:.2}", x)
@@ -1,9 +1,15 @@
 //! Utilities for network testing.
 
+pub mod chain;
+pub mod connection_stats;
 pub mod fuzzing;
+pub mod inbound_queue;
 pub mod message_filter;
 pub mod metrics;
+pub mod rpc;
+pub mod swarm;
 pub mod synthetic_node;
+pub mod traffic_shaping;
 
 use std::time::Duration;
 
@@ -0,0 +1,488 @@
+//! A JSON-RPC client, for introspecting the node under test (e.g. its peers or ban list).
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+use crate::protocol::payload::{block::Block, codec::Codec};
+
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Default time allowed for an RPC call to complete.
+const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An [`Error`](std::error::Error) type for the calls of an [`RpcClient`].
+pub enum RpcError {
+    /// The node returned an error for the call.
+    Rpc { code: i64, message: String },
+    /// The node replied with an HTTP error and no JSON-RPC response (e.g. `401` for invalid
+    /// credentials).
+    Http(u16),
+    /// The response couldn't be decoded, or didn't have the expected type.
+    Decode(String),
+    /// An [io::Error] occurred, e.g. while connecting to the node.
+    IoErr(io::Error),
+}
+
+impl std::fmt::Debug for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            RpcError::Rpc { code, message } => format!("RPC error {}: {}", code, message),
+            RpcError::Http(status) => format!("HTTP error {}", status),
+            RpcError::Decode(reason) => format!("Failed to decode the response: {}", reason),
+            RpcError::IoErr(err) => format!("{:?}", err),
+        };
+
+        f.write_str(&str)
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{:?}", self))
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<io::Error> for RpcError {
+    fn from(err: io::Error) -> Self {
+        RpcError::IoErr(err)
+    }
+}
+
+impl From<RpcError> for io::Error {
+    fn from(original: RpcError) -> Self {
+        let kind = match original {
+            RpcError::Rpc { .. } | RpcError::Http(_) => ErrorKind::Other,
+            RpcError::Decode(_) => ErrorKind::InvalidData,
+            RpcError::IoErr(err) => return err,
+        };
+
+        io::Error::new(kind, original)
+    }
+}
+
+/// A peer of the node, as returned by `getpeerinfo`.
+///
+/// The fields which aren't common to the node kinds (or versions) are kept in `other`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeerInfo {
+    /// The address of the peer.
+    pub addr: String,
+    #[serde(default)]
+    pub inbound: bool,
+    /// The protocol version of the peer.
+    pub version: Option<u32>,
+    /// The user agent of the peer.
+    pub subver: Option<String>,
+    /// The misbehaviour score of the peer.
+    pub banscore: Option<i64>,
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+
+/// An entry of the node's ban list, as returned by `listbanned`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BannedEntry {
+    /// The banned address or subnet.
+    pub address: String,
+    /// The end of the ban, as a unix timestamp.
+    pub banned_until: Option<i64>,
+    /// The start of the ban, as a unix timestamp.
+    pub ban_created: Option<i64>,
+    pub ban_reason: Option<String>,
+}
+
+/// The command of an `addnode` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddNodeCommand {
+    /// Adds the peer to the node's list of peers to connect to.
+    Add,
+    /// Removes the peer from the node's list of peers to connect to.
+    Remove,
+    /// Tries to connect to the peer once.
+    OneTry,
+}
+
+impl AddNodeCommand {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::OneTry => "onetry",
+        }
+    }
+}
+
+/// A client for the node's JSON-RPC interface (over HTTP), see
+/// [`Node::rpc_client`](crate::setup::node::Node::rpc_client).
+///
+/// Each call is made on a new connection.
+#[derive(Debug)]
+pub struct RpcClient {
+    addr: SocketAddr,
+    /// The `user:password` credentials, if any.
+    credentials: Option<String>,
+    timeout: Duration,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    /// Creates a client for the JSON-RPC server at `addr`, with optional `(user, password)`
+    /// credentials.
+    pub fn new(addr: SocketAddr, credentials: Option<(&str, &str)>) -> Self {
+        Self {
+            addr,
+            credentials: credentials.map(|(user, password)| format!("{}:{}", user, password)),
+            timeout: DEFAULT_RPC_TIMEOUT,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the time allowed for a call to complete, defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the address of the JSON-RPC server.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the node's peers.
+    pub async fn get_peer_info(&self) -> Result<Vec<PeerInfo>, RpcError> {
+        self.call("getpeerinfo", json!([])).await
+    }
+
+    /// Returns the height of the node's best chain.
+    pub async fn get_block_count(&self) -> Result<u64, RpcError> {
+        self.call("getblockcount", json!([])).await
+    }
+
+    /// Returns the (hex encoded) ids of the transactions in the node's mempool.
+    pub async fn get_raw_mempool(&self) -> Result<Vec<String>, RpcError> {
+        self.call("getrawmempool", json!([])).await
+    }
+
+    /// Returns the node's ban list.
+    pub async fn list_banned(&self) -> Result<Vec<BannedEntry>, RpcError> {
+        self.call("listbanned", json!([])).await
+    }
+
+    /// Adds, removes or tries a peer of the node.
+    pub async fn add_node(
+        &self,
+        addr: SocketAddr,
+        command: AddNodeCommand,
+    ) -> Result<(), RpcError> {
+        self.call("addnode", json!([addr.to_string(), command.as_str()]))
+            .await
+    }
+
+    /// Disconnects the node from the peer.
+    pub async fn disconnect_node(&self, addr: SocketAddr) -> Result<(), RpcError> {
+        self.call("disconnectnode", json!([addr.to_string()])).await
+    }
+
+    /// Mines `n` blocks (on regtest), returns their (hex encoded) hashes.
+    pub async fn generate(&self, n: u32) -> Result<Vec<String>, RpcError> {
+        self.call("generate", json!([n])).await
+    }
+
+    /// Submits the block to the node, returns the reason it was rejected, if it was.
+    pub async fn submit_block(&self, block: &Block) -> Result<Option<String>, RpcError> {
+        let mut bytes = Vec::new();
+        block.encode(&mut bytes)?;
+
+        self.call("submitblock", json!([hex::encode(bytes)])).await
+    }
+
+    /// Calls the method with the given (JSON array) parameters, and decodes its result.
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({
+            "jsonrpc": "1.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+
+        let (status, body) = timeout(self.timeout, self.post(&request))
+            .await
+            .map_err(|_| io::Error::new(ErrorKind::TimedOut, "RPC call timed out"))??;
+
+        // Errors are also reported as JSON-RPC responses, with an HTTP error status.
+        let mut response: Value = match serde_json::from_slice(&body) {
+            Ok(response) => response,
+            Err(_) if status != 200 => return Err(RpcError::Http(status)),
+            Err(e) => return Err(RpcError::Decode(e.to_string())),
+        };
+
+        let error = response.get_mut("error").map(Value::take);
+        if let Some(error) = error.filter(|error| !error.is_null()) {
+            return Err(RpcError::Rpc {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            });
+        }
+
+        let result = response.get_mut("result").map(Value::take);
+        serde_json::from_value(result.unwrap_or_default())
+            .map_err(|e| RpcError::Decode(e.to_string()))
+    }
+
+    /// Posts the request, returns the response's status and body.
+    async fn post(&self, body: &str) -> io::Result<(u16, Vec<u8>)> {
+        let mut stream = TcpStream::connect(self.addr).await?;
+
+        let mut request = format!(
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.addr,
+            body.len()
+        );
+        if let Some(ref credentials) = self.credentials {
+            request.push_str(&format!(
+                "Authorization: Basic {}\r\n",
+                base64(credentials.as_bytes())
+            ));
+        }
+        request.push_str("\r\n");
+        request.push_str(body);
+        stream.write_all(request.as_bytes()).await?;
+
+        // The connection is closed by the server once it has replied.
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        parse_http_response(&response)
+    }
+}
+
+/// Parses an HTTP response, returns its status and body.
+fn parse_http_response(response: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "invalid HTTP response");
+
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(invalid)?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let mut body = &response[header_end + 4..];
+
+    // e.g. `HTTP/1.1 200 OK`
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let content_length = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse::<usize>().ok())
+            .flatten()
+    });
+    if let Some(length) = content_length {
+        body = body.get(..length).ok_or_else(invalid)?;
+    }
+
+    Ok((status, body.to_vec()))
+}
+
+/// Encodes the bytes as standard (padded) base64, for the `Authorization` header.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::{net::TcpListener, task::JoinHandle};
+
+    /// Serves a single request with the given status and body, returns the request.
+    async fn mock_server(status: &'static str, body: Value) -> (SocketAddr, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            // Read the head, then the body according to its length.
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            let body_len = loop {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+                let text = String::from_utf8_lossy(&request).into_owned();
+                if let Some(head_end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap();
+                    break head_end + 4 + length;
+                }
+            };
+            while request.len() < body_len {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+            }
+
+            let body = body.to_string();
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+
+            String::from_utf8(request).unwrap()
+        });
+
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn typed_results_are_decoded() {
+        let (addr, server) = mock_server(
+            "200 OK",
+            json!({
+                "result": [{
+                    "id": 3,
+                    "addr": "127.0.0.1:18233",
+                    "inbound": true,
+                    "version": 170013,
+                    "subver": "/MagicBean:4.4.1/",
+                    "banscore": 10
+                }],
+                "error": null,
+                "id": 0
+            }),
+        )
+        .await;
+
+        let client = RpcClient::new(addr, Some(("user", "pass")));
+        let peers = client.get_peer_info().await.unwrap();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].addr, "127.0.0.1:18233");
+        assert!(peers[0].inbound);
+        assert_eq!(peers[0].banscore, Some(10));
+        assert_eq!(peers[0].other["id"], json!(3));
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST / HTTP/1.1\r\n"));
+        // base64("user:pass")
+        assert!(request.contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+        let body: Value = serde_json::from_str(request.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["method"], "getpeerinfo");
+        assert_eq!(body["params"], json!([]));
+
+        let (addr, server) =
+            mock_server("200 OK", json!({"result": null, "error": null, "id": 0})).await;
+        let client = RpcClient::new(addr, None);
+        let peer = "1.2.3.4:8233".parse().unwrap();
+        client.add_node(peer, AddNodeCommand::OneTry).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(!request.contains("Authorization"));
+        assert!(request.ends_with(r#""params":["1.2.3.4:8233","onetry"]}"#));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn errors_are_reported() {
+        let (addr, _server) = mock_server(
+            "500 Internal Server Error",
+            json!({
+                "result": null,
+                "error": {"code": -24, "message": "Node has not been added."},
+                "id": 0
+            }),
+        )
+        .await;
+        let error = RpcClient::new(addr, None)
+            .disconnect_node("1.2.3.4:8233".parse().unwrap())
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(error, RpcError::Rpc { code: -24, .. });
+
+        // A result of the wrong type.
+        let (addr, _server) =
+            mock_server("200 OK", json!({"result": "ten", "error": null, "id": 0})).await;
+        let error = RpcClient::new(addr, None)
+            .get_block_count()
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(error, RpcError::Decode(_));
+
+        // Invalid credentials are only an HTTP status.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).await;
+            stream
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        let error = RpcClient::new(addr, None)
+            .get_raw_mempool()
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(error, RpcError::Http(401));
+    }
+
+    #[test]
+    #[ignore]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
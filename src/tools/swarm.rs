@@ -0,0 +1,274 @@
+//! Runs a behaviour on many synthetic nodes at once, for large-scale scenarios.
+
+use metrics::Key;
+use tokio::{
+    sync::Semaphore,
+    task::JoinHandle,
+    time::{sleep, Duration, Instant},
+};
+
+use crate::tools::{
+    metrics::tables::duration_as_ms,
+    synthetic_node::{SyntheticNode, SyntheticNodeBuilder},
+};
+
+use std::{future::Future, io, sync::Arc};
+
+/// Name of the default histogram metric recording the latencies reported by the peers, in ms.
+pub const METRIC_LATENCY: &str = "swarm_latency";
+/// Name of the counter metric recording the peers whose behaviour completed.
+pub const METRIC_COMPLETED: &str = "swarm_completed";
+/// Name of the counter metric recording the peers which failed to build or whose behaviour
+/// panicked.
+pub const METRIC_FAILED: &str = "swarm_failed";
+
+/// A peer of a [`Swarm`], handed to the behaviour.
+pub struct Peer {
+    /// The index of the peer in the swarm.
+    pub index: usize,
+    pub node: SyntheticNode,
+    latency_metric: &'static str,
+}
+
+impl Peer {
+    /// Records a latency to the swarm's latency histogram (if a metrics recorder is installed).
+    pub fn record_latency(&self, latency: Duration) {
+        if let Some(recorder) = metrics::try_recorder() {
+            recorder.record_histogram(
+                &Key::from_name(self.latency_metric),
+                duration_as_ms(latency),
+            );
+        }
+    }
+}
+
+/// A builder for [`Swarm`].
+#[derive(Debug, Clone)]
+pub struct SwarmBuilder {
+    node_builder: SyntheticNodeBuilder,
+    size: usize,
+    stagger: Duration,
+    concurrency_limit: Option<usize>,
+    latency_metric: &'static str,
+}
+
+impl SwarmBuilder {
+    /// Creates a builder for a [`Swarm`] of `size` peers, with nodes built from `node_builder`.
+    pub fn new(node_builder: SyntheticNodeBuilder, size: usize) -> Self {
+        Self {
+            node_builder,
+            size,
+            stagger: Duration::ZERO,
+            concurrency_limit: None,
+            latency_metric: METRIC_LATENCY,
+        }
+    }
+
+    /// Delays the start of each peer by `stagger` relative to the previous one.
+    pub fn with_stagger(mut self, stagger: Duration) -> Self {
+        self.stagger = stagger;
+        self
+    }
+
+    /// Limits the number of peers running at the same time, the others wait for their turn
+    /// before building their node.
+    pub fn with_concurrency_limit(mut self, limit: usize) -> Self {
+        assert!(limit > 0, "concurrency limit must be non-zero");
+
+        self.concurrency_limit = Some(limit);
+        self
+    }
+
+    /// Sets the name of the histogram recording the latencies, defaults to [`METRIC_LATENCY`].
+    pub fn with_latency_metric(mut self, name: &'static str) -> Self {
+        self.latency_metric = name;
+        self
+    }
+
+    /// Starts the swarm, running the behaviour on each peer.
+    ///
+    /// The swarm's metrics are (re)registered if a metrics recorder is installed.
+    pub fn start<F, Fut, T>(&self, behaviour: F) -> Swarm<T>
+    where
+        F: Fn(Peer) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        if let Some(recorder) = metrics::try_recorder() {
+            recorder.register_histogram(&Key::from_name(self.latency_metric), None, None);
+            recorder.register_counter(&Key::from_name(METRIC_COMPLETED), None, None);
+            recorder.register_counter(&Key::from_name(METRIC_FAILED), None, None);
+        }
+
+        let behaviour = Arc::new(behaviour);
+        let permits = Arc::new(Semaphore::new(
+            self.concurrency_limit.unwrap_or(Semaphore::MAX_PERMITS),
+        ));
+
+        let handles = (0..self.size)
+            .map(|index| {
+                let behaviour = behaviour.clone();
+                let permits = permits.clone();
+                let node_builder = self.node_builder.clone();
+                let delay = self.stagger * index as u32;
+                let latency_metric = self.latency_metric;
+
+                tokio::spawn(async move {
+                    sleep(delay).await;
+                    // Safe, as the semaphore is never closed.
+                    let _permit = permits.acquire().await.unwrap();
+
+                    let node = node_builder.build().await?;
+                    Ok(behaviour(Peer {
+                        index,
+                        node,
+                        latency_metric,
+                    })
+                    .await)
+                })
+            })
+            .collect();
+
+        Swarm {
+            handles,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// A set of [`SyntheticNode`]s sharing the same configuration, each running the same behaviour,
+/// see [`SwarmBuilder`].
+///
+/// Dropping the swarm aborts the peers which are still running, shutting down their nodes.
+pub struct Swarm<T> {
+    handles: Vec<JoinHandle<io::Result<T>>>,
+    start: Instant,
+}
+
+impl<T> Swarm<T> {
+    /// Returns the number of peers.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if the swarm has no peers.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Waits for all of the peers, returns their results (indexed as the peers) and the time
+    /// since the swarm started.
+    ///
+    /// A peer's result is an error if its node couldn't be built or its behaviour panicked.
+    pub async fn join(mut self) -> (Vec<io::Result<T>>, Duration) {
+        let mut results = Vec::with_capacity(self.handles.len());
+
+        for handle in std::mem::take(&mut self.handles) {
+            let result = match handle.await {
+                Ok(result) => result,
+                Err(error) => Err(io::Error::other(error)),
+            };
+
+            let metric = match result {
+                Ok(_) => METRIC_COMPLETED,
+                Err(_) => METRIC_FAILED,
+            };
+            if let Some(recorder) = metrics::try_recorder() {
+                recorder.increment_counter(&Key::from_name(metric), 1);
+            }
+
+            results.push(result);
+        }
+
+        (results, self.start.elapsed())
+    }
+}
+
+impl<T> Drop for Swarm<T> {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::message::Message,
+        tools::{synthetic_node::Event, TIMEOUT},
+        wait_until,
+    };
+
+    #[tokio::test]
+    #[ignore]
+    async fn peers_run_staggered_within_limit() {
+        const PEERS: usize = 6;
+        const STAGGER: Duration = Duration::from_millis(10);
+
+        let responder = SyntheticNode::builder()
+            .with_all_auto_reply()
+            .build()
+            .await
+            .unwrap();
+        let responder_addr = responder.listening_addr();
+
+        let running = Arc::new(Semaphore::new(2));
+        let swarm = SwarmBuilder::new(SyntheticNode::builder(), PEERS)
+            .with_stagger(STAGGER)
+            .with_concurrency_limit(2)
+            .start(move |mut peer| {
+                let running = running.clone();
+                async move {
+                    // Fails if more than 2 peers run at once.
+                    let _running = running.try_acquire().unwrap();
+
+                    peer.node.connect(responder_addr).await.unwrap();
+                    let start = Instant::now();
+                    peer.node
+                        .ping_pong_timeout(responder_addr, TIMEOUT)
+                        .await
+                        .unwrap();
+                    peer.record_latency(start.elapsed());
+
+                    peer.index
+                }
+            });
+        assert_eq!(swarm.len(), PEERS);
+
+        let (results, elapsed) = swarm.join().await;
+        let indices = results
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(indices, (0..PEERS).collect::<Vec<_>>());
+        assert!(elapsed >= STAGGER * (PEERS - 1) as u32);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn dropping_the_swarm_shuts_peers_down() {
+        let mut listener = SyntheticNode::builder().build().await.unwrap();
+        let listener_addr = listener.listening_addr();
+
+        let swarm = SwarmBuilder::new(SyntheticNode::builder(), 3).start(move |peer| async move {
+            peer.node.connect(listener_addr).await.unwrap();
+            // Never completes on its own.
+            std::future::pending::<Message>().await
+        });
+
+        wait_until!(TIMEOUT, listener.num_connected() == 3);
+        drop(swarm);
+        wait_until!(TIMEOUT, listener.num_connected() == 0);
+
+        // The listener saw every peer disconnect.
+        let mut disconnected = 0;
+        while let Ok((_, event)) = listener.events().try_recv() {
+            if let Event::Disconnected { .. } = event {
+                disconnected += 1;
+            }
+        }
+        assert_eq!(disconnected, 3);
+    }
+}
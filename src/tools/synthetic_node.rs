@@ -2,30 +2,58 @@
 
 use crate::{
     protocol::{
-        message::{constants::HEADER_LEN, Message, MessageHeader},
-        payload::{codec::Codec, Nonce, Version},
+        clock::Clock,
+        message::{
+            constants::{HEADER_LEN, MAX_MESSAGE_LEN},
+            Message, MessageHeader,
+        },
+        payload::{codec::Codec, Nonce, ProtocolVersion, Version},
+    },
+    tools::{
+        chain::Chain,
+        connection_stats::{self, ConnectionStats, Direction},
+        inbound_queue::{InboundQueue, QueuePolicy},
+        message_filter::{Filter, MessageFilter},
+        traffic_shaping::TrafficShaping,
     },
-    tools::message_filter::{Filter, MessageFilter},
 };
 
-use assert_matches::assert_matches;
+use histogram::Histogram;
+use parking_lot::RwLock;
 use pea2pea::{
     connections::ConnectionSide,
     protocols::{Handshaking, Reading, Writing},
     Connection, KnownPeers, Node, NodeConfig, Pea2Pea,
 };
 use tokio::{
-    sync::mpsc::{self, Receiver, Sender},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
+    net::TcpSocket,
+    sync::mpsc::{self, Sender, UnboundedReceiver, UnboundedSender},
     time::timeout,
 };
 use tracing::*;
 
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    convert::TryInto,
+    fmt,
     io::{self, Cursor, Error, ErrorKind},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-    time::Duration,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
+/// The length of the time prefixed to the queued outbound messages, see [`InnerNode::queue`].
+const QUEUED_AT_LEN: usize = 8;
+
+lazy_static::lazy_static! {
+    /// The reference point of the times the outbound messages are queued at.
+    static ref QUEUE_EPOCH: Instant = Instant::now();
+}
+
 /// An [`Error`](std::error::Error) type for [`SyntheticNode::ping_pong_timeout`]
 pub enum PingPongError {
     /// The connection was aborted during the [`Ping`](Message::Ping)-[`Pong`](Message::Pong) exchange.
@@ -76,10 +104,320 @@ impl From<PingPongError> for io::Error {
                 ErrorKind::TimedOut,
                 format!("Timeout after {0:.3}s", duration.as_secs_f64()),
             ),
-            Unexpected(msg) => Error::new(
-                ErrorKind::Other,
-                format!("Expected Pong, received {:?}", msg),
-            ),
+            Unexpected(msg) => Error::other(format!("Expected Pong, received {:?}", msg)),
+        }
+    }
+}
+
+/// An [`Error`](std::error::Error) type for the handshake performed by a [`SyntheticNode`].
+pub enum HandshakeError {
+    /// A message other than the one expected at this stage of the handshake was received.
+    UnexpectedMessage(Box<Message>),
+    /// The handshake did not complete before the timeout expired.
+    Timeout(Duration),
+    /// A message received during the handshake could not be decoded.
+    Decode(io::Error),
+    /// The peer closed the connection during the handshake.
+    PeerClosed,
+    /// The peer's [`Version`] is older than the minimum accepted.
+    VersionTooOld(ProtocolVersion),
+    /// An [io::Error] occurred, e.g. while establishing the connection.
+    IoErr(io::Error),
+}
+
+impl HandshakeError {
+    /// Classifies an [io::Error] from reading or writing the stream.
+    fn from_stream_err(err: io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe => HandshakeError::PeerClosed,
+            _ => HandshakeError::IoErr(err),
+        }
+    }
+
+    /// Recovers the [`HandshakeError`] carried by an [io::Error] returned from `pea2pea`.
+    fn from_connect_err(err: io::Error, handshake_timeout: Duration) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            // Safe, as the inner error has just been checked.
+            *err.into_inner().unwrap().downcast::<Self>().unwrap()
+        } else if err.kind() == ErrorKind::TimedOut {
+            HandshakeError::Timeout(handshake_timeout)
+        } else {
+            HandshakeError::IoErr(err)
+        }
+    }
+
+    /// Copies the error, [io::Error]s only keep their kind and description.
+    fn duplicate(&self) -> Self {
+        let copy = |err: &io::Error| Error::new(err.kind(), err.to_string());
+
+        match self {
+            HandshakeError::UnexpectedMessage(msg) => {
+                HandshakeError::UnexpectedMessage(msg.clone())
+            }
+            HandshakeError::Timeout(duration) => HandshakeError::Timeout(*duration),
+            HandshakeError::Decode(err) => HandshakeError::Decode(copy(err)),
+            HandshakeError::PeerClosed => HandshakeError::PeerClosed,
+            HandshakeError::VersionTooOld(version) => HandshakeError::VersionTooOld(*version),
+            HandshakeError::IoErr(err) => HandshakeError::IoErr(copy(err)),
+        }
+    }
+}
+
+impl std::fmt::Debug for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            HandshakeError::UnexpectedMessage(msg) => {
+                format!("Unexpected message during handshake: {:?}", msg)
+            }
+            HandshakeError::Timeout(duration) => {
+                format!("Handshake timeout after {0:.3}s", duration.as_secs_f32())
+            }
+            HandshakeError::Decode(err) => format!("Failed to decode message: {:?}", err),
+            HandshakeError::PeerClosed => "Connection closed by peer".to_string(),
+            HandshakeError::VersionTooOld(version) => {
+                format!("Obsolete peer version: {:?}", version)
+            }
+            HandshakeError::IoErr(err) => format!("{:?}", err),
+        };
+
+        f.write_str(&str)
+    }
+}
+
+impl std::fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{:?}", self))
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+impl From<HandshakeError> for io::Error {
+    fn from(original: HandshakeError) -> Self {
+        use HandshakeError::*;
+        let kind = match original {
+            UnexpectedMessage(_) | Decode(_) | VersionTooOld(_) => ErrorKind::InvalidData,
+            Timeout(_) => ErrorKind::TimedOut,
+            PeerClosed => ErrorKind::ConnectionAborted,
+            IoErr(err) => return err,
+        };
+
+        Error::new(kind, original)
+    }
+}
+
+/// An [`Error`](std::error::Error) type for the expectations of a [`SyntheticNode`], e.g.
+/// [`SyntheticNode::expect`].
+///
+/// Each variant contains the messages which were skipped while waiting.
+pub enum ExpectError {
+    /// The timeout expired before a matching message was received.
+    Timeout {
+        duration: Duration,
+        skipped: Vec<Message>,
+    },
+    /// The connection was terminated before a matching message was received.
+    ConnectionAborted { skipped: Vec<Message> },
+    /// A message was received which didn't match, and the skip limit had already been reached.
+    Unexpected {
+        unexpected: Box<Message>,
+        skipped: Vec<Message>,
+    },
+    /// A frame was received which couldn't be decoded (see [`Inbound::Raw`]).
+    Undecodable {
+        header: MessageHeader,
+        error: io::Error,
+        skipped: Vec<Message>,
+    },
+}
+
+impl ExpectError {
+    /// Returns the messages which were skipped before the expectation failed.
+    pub fn skipped(&self) -> &[Message] {
+        match self {
+            ExpectError::Timeout { skipped, .. }
+            | ExpectError::ConnectionAborted { skipped }
+            | ExpectError::Unexpected { skipped, .. }
+            | ExpectError::Undecodable { skipped, .. } => skipped,
+        }
+    }
+}
+
+impl std::fmt::Debug for ExpectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            ExpectError::Timeout { duration, .. } => {
+                format!("Timeout after {0:.3}s", duration.as_secs_f32())
+            }
+            ExpectError::ConnectionAborted { .. } => "Connection aborted".to_string(),
+            ExpectError::Unexpected { unexpected, .. } => {
+                format!("Unexpected message {:?}", unexpected)
+            }
+            ExpectError::Undecodable { header, error, .. } => {
+                format!("Undecodable frame {:?}: {:?}", header, error)
+            }
+        };
+
+        match self.skipped() {
+            [] => f.write_str(&str),
+            skipped => write!(f, "{}, after skipping {:?}", str, skipped),
+        }
+    }
+}
+
+impl std::fmt::Display for ExpectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{:?}", self))
+    }
+}
+
+impl std::error::Error for ExpectError {}
+
+impl From<ExpectError> for io::Error {
+    fn from(original: ExpectError) -> Self {
+        let kind = match original {
+            ExpectError::Timeout { .. } => ErrorKind::TimedOut,
+            ExpectError::ConnectionAborted { .. } => ErrorKind::ConnectionAborted,
+            ExpectError::Unexpected { .. } => ErrorKind::Other,
+            ExpectError::Undecodable { .. } => ErrorKind::InvalidData,
+        };
+
+        Error::new(kind, original)
+    }
+}
+
+/// An item on the inbound queue of a [`SyntheticNode`].
+#[derive(Debug)]
+pub enum Inbound {
+    /// A decoded message.
+    Message(Box<Message>),
+    /// A frame which couldn't be decoded, only delivered if enabled with
+    /// [`SyntheticNodeBuilder::with_raw_frames`].
+    Raw {
+        header: MessageHeader,
+        /// The (undecodable) message body.
+        bytes: Vec<u8>,
+        error: io::Error,
+    },
+}
+
+/// A message which met an expectation of a [`SyntheticNode`], e.g. [`SyntheticNode::expect`].
+#[derive(Debug)]
+pub struct Expected {
+    /// The matching message.
+    pub message: Message,
+    /// The messages received (in order) before the matching message.
+    pub skipped: Vec<Message>,
+}
+
+/// An event related to the connections of a [`SyntheticNode`], see [`SyntheticNode::events`].
+///
+/// A connection's events start with [`Connected`](Event::Connected) and end with either
+/// [`HandshakeFailed`](Event::HandshakeFailed) or [`Disconnected`](Event::Disconnected).
+#[derive(Debug)]
+pub enum Event {
+    /// A connection was established, before the handshake (if any) starts.
+    Connected {
+        addr: SocketAddr,
+        /// The node's side of the connection.
+        side: ConnectionSide,
+    },
+    /// The handshake with a peer completed, only emitted if the node performs a handshake.
+    Handshaken { addr: SocketAddr },
+    /// The handshake with a peer failed, which terminates the connection.
+    ///
+    /// Failures of handshakes initiated by the node are also returned by
+    /// [`SyntheticNode::connect`], in which case the event's [io::Error]s (if any) only keep their
+    /// kind and description.
+    HandshakeFailed {
+        addr: SocketAddr,
+        error: HandshakeError,
+    },
+    /// A keepalive [`Ping`](Message::Ping) wasn't answered before the timeout, see
+    /// [`SyntheticNodeBuilder::with_keepalive`].
+    PongMissing { addr: SocketAddr, nonce: Nonce },
+    /// An established connection was terminated.
+    Disconnected {
+        addr: SocketAddr,
+        reason: DisconnectReason,
+    },
+}
+
+/// The reason a connection of a [`SyntheticNode`] was terminated, see [`Event::Disconnected`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisconnectReason {
+    /// The peer closed the connection.
+    Eof,
+    /// The connection was reset or aborted.
+    Reset,
+    /// The peer sent a frame which couldn't be decoded (or was too large).
+    Decode,
+    /// The node closed the connection, see [`SyntheticNode::disconnect`] and
+    /// [`SyntheticNode::shut_down`].
+    LocalShutdown,
+    /// Any other fatal [io::Error] while reading or writing.
+    Io(ErrorKind),
+}
+
+impl From<ErrorKind> for DisconnectReason {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::UnexpectedEof => Self::Eof,
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => Self::Reset,
+            ErrorKind::InvalidData => Self::Decode,
+            kind => Self::Io(kind),
+        }
+    }
+}
+
+/// The IP a [`SyntheticNode`] binds to and listens on, see
+/// [`SyntheticNodeBuilder::with_listener_ip`]. The connections initiated by the node also come
+/// from this IP, as long as it's of the same family as the target's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListenerIp {
+    /// The same IP for every node.
+    Fixed(IpAddr),
+    /// A distinct IP from 127.0.0.0/8 for each node, starting from `127.0.0.2`.
+    Spread,
+    /// Alternates between a distinct IP from 127.0.0.0/8 (as [`Spread`](Self::Spread)) and `::1`.
+    SpreadDualStack,
+}
+
+impl Default for ListenerIp {
+    fn default() -> Self {
+        Self::Fixed(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+}
+
+impl ListenerIp {
+    /// Returns the IP for the next node.
+    fn next_ip(&self) -> IpAddr {
+        // Shared by all builders, so that nodes get distinct IPs across builders.
+        static NEXT_SPREAD: AtomicU32 = AtomicU32::new(0);
+        static NEXT_DUAL_STACK: AtomicU32 = AtomicU32::new(0);
+
+        // 127.0.0.0 and 127.0.0.1 are skipped, as is 127.255.255.255.
+        let spread_ip = || {
+            let offset = NEXT_SPREAD.fetch_add(1, Ordering::Relaxed) % 0x00ff_fffd + 2;
+            IpAddr::V4(Ipv4Addr::from(0x7f00_0000 | offset))
+        };
+
+        match self {
+            Self::Fixed(ip) => *ip,
+            Self::Spread => spread_ip(),
+            Self::SpreadDualStack => {
+                if NEXT_DUAL_STACK
+                    .fetch_add(1, Ordering::Relaxed)
+                    .is_multiple_of(2)
+                {
+                    spread_ip()
+                } else {
+                    IpAddr::V6(Ipv6Addr::LOCALHOST)
+                }
+            }
         }
     }
 }
@@ -108,24 +446,91 @@ pub enum Handshake {
     VersionOnly,
 }
 
+/// Constructs the [`Version`] sent during the handshake from its `addr_recv` and `addr_from`
+/// addresses, see [`SyntheticNodeBuilder::with_version`].
+#[derive(Clone)]
+struct VersionFn(Arc<dyn Fn(SocketAddr, SocketAddr) -> Version + Send + Sync>);
+
+impl fmt::Debug for VersionFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VersionFn")
+    }
+}
+
+/// Default time allowed for a handshake to complete.
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Options for the handshake performed by a [`SyntheticNode`].
+///
+/// The [`Verack`] related options only apply to [`Handshake::Full`].
+///
+/// [`Verack`]: enum@crate::protocol::message::Message::Verack
+#[derive(Debug, Clone)]
+struct HandshakeOptions {
+    /// Time allowed for the handshake to complete.
+    timeout: Duration,
+    /// The minimum protocol version accepted from the peer.
+    min_version: Option<ProtocolVersion>,
+    /// Delay before the [`Verack`](Message::Verack) is written.
+    verack_delay: Option<Duration>,
+    /// Write [`Verack`](Message::Verack) before [`Version`](Message::Version).
+    verack_first: bool,
+    /// Messages written in between [`Version`](Message::Version) and [`Verack`](Message::Verack).
+    interleaved: Vec<Message>,
+}
+
+impl Default for HandshakeOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            min_version: None,
+            verack_delay: None,
+            verack_first: false,
+            interleaved: Vec::new(),
+        }
+    }
+}
+
 /// A builder for [`SyntheticNode`].
 #[derive(Debug, Clone)]
 pub struct SyntheticNodeBuilder {
     network_config: Option<NodeConfig>,
+    listener_ip: ListenerIp,
     handshake: Option<Handshake>,
+    handshake_options: HandshakeOptions,
+    version: Option<VersionFn>,
+    clock: Clock,
     message_filter: MessageFilter,
+    chain: Option<Arc<Chain>>,
+    skip_limit: usize,
+    raw_frames: bool,
+    byte_tap: bool,
+    traffic_shaping: TrafficShaping,
+    stats_metrics: bool,
+    inbound_capacity: usize,
+    inbound_policy: QueuePolicy,
+    keepalive: Option<Keepalive>,
 }
 
 impl Default for SyntheticNodeBuilder {
     fn default() -> Self {
         Self {
-            network_config: Some(NodeConfig {
-                // Set localhost as the default IP.
-                listener_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
-                ..Default::default()
-            }),
+            network_config: None,
+            listener_ip: Default::default(),
             handshake: None,
+            handshake_options: Default::default(),
+            version: None,
+            clock: Default::default(),
             message_filter: MessageFilter::with_all_disabled(),
+            chain: None,
+            skip_limit: 0,
+            raw_frames: false,
+            byte_tap: false,
+            traffic_shaping: Default::default(),
+            stats_metrics: false,
+            inbound_capacity: 100,
+            inbound_policy: Default::default(),
+            keepalive: None,
         }
     }
 }
@@ -133,20 +538,67 @@ impl Default for SyntheticNodeBuilder {
 impl SyntheticNodeBuilder {
     /// Creates a [`SyntheticNode`] with the current configuration
     pub async fn build(&self) -> io::Result<SyntheticNode> {
+        // pea2pea's handshake limit only serves as a backstop, the handshake enforces its own
+        // timeout so that it can be reported as a `HandshakeError`.
+        let mut network_config = self.network_config.clone().unwrap_or_default();
+        network_config.listener_ip = self.listener_ip.next_ip();
+        network_config.max_handshake_time_ms =
+            2 * self.handshake_options.timeout.as_millis() as u64;
+
         // Create the pea2pea node from the config.
-        let node = Node::new(self.network_config.clone()).await?;
+        let node = Node::new(Some(network_config)).await?;
+
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let (tap_tx, tap_rx) = mpsc::unbounded_channel();
+
+        // The chain replies to its queries regardless of the other filters.
+        let message_filter = match self.chain {
+            Some(ref chain) => self
+                .message_filter
+                .clone()
+                .with_chain_filters(chain.clone()),
+            None => self.message_filter.clone(),
+        };
 
-        // Inbound channel size of 100 messages.
-        let (tx, rx) = mpsc::channel(100);
-        let inner_node = InnerNode::new(node, tx, self.message_filter.clone(), self.handshake);
+        let inner_node = InnerNode {
+            node,
+            handshake: self.handshake,
+            handshake_options: self.handshake_options.clone(),
+            version: self.version.clone(),
+            clock: self.clock,
+            inbound: Arc::new(InboundQueue::new(
+                self.inbound_capacity,
+                self.inbound_policy,
+            )),
+            events_tx,
+            tap_tx: self.byte_tap.then_some(tap_tx),
+            message_filter,
+            raw_frames: self.raw_frames,
+            traffic_shaping: Arc::new(RwLock::new(TrafficShapingState {
+                default: self.traffic_shaping.clone(),
+                connections: HashMap::new(),
+            })),
+            paused_reads: Default::default(),
+            stats: Default::default(),
+            stats_metrics: self.stats_metrics,
+            connections: Default::default(),
+            keepalive: self.keepalive,
+            keepalives: Default::default(),
+        };
 
-        // Enable the read and write protocols
+        // Enable the handshake, read and write protocols. Handshaking is enabled even without a
+        // handshake, as it reports the new connections.
+        inner_node.enable_handshaking();
         inner_node.enable_reading();
         inner_node.enable_writing();
 
         Ok(SyntheticNode {
             inner_node,
-            inbound_rx: rx,
+            events_rx,
+            tap_rx,
+            chain: self.chain.clone(),
+            pending: VecDeque::new(),
+            skip_limit: self.skip_limit,
         })
     }
 
@@ -163,6 +615,24 @@ impl SyntheticNodeBuilder {
         Ok((nodes, addrs))
     }
 
+    /// Creates `n` [`SyntheticNode`]'s with the current configuration, each listening on a
+    /// distinct IP (see [`ListenerIp::Spread`]), and also returns their listening address.
+    pub async fn build_n_distinct(
+        &self,
+        n: usize,
+    ) -> io::Result<(Vec<SyntheticNode>, Vec<SocketAddr>)> {
+        self.clone()
+            .with_listener_ip(ListenerIp::Spread)
+            .build_n(n)
+            .await
+    }
+
+    /// Sets the IP the node binds to and listens on, defaults to `127.0.0.1`.
+    pub fn with_listener_ip(mut self, listener_ip: ListenerIp) -> Self {
+        self.listener_ip = listener_ip;
+        self
+    }
+
     /// Sets the node's [`MessageFilter`] to [`Filter::AutoReply`].
     pub fn with_all_auto_reply(mut self) -> Self {
         self.message_filter = MessageFilter::with_all_auto_reply();
@@ -181,12 +651,152 @@ impl SyntheticNodeBuilder {
         self
     }
 
+    /// Sets the function used to construct the [`Version`] sent during the handshake.
+    ///
+    /// It is called with the `addr_recv` and `addr_from` addresses (in that order), on both the
+    /// initiator and responder side. Defaults to [`Version::new_with_clock`] with the node's
+    /// clock.
+    pub fn with_version<F>(mut self, version: F) -> Self
+    where
+        F: Fn(SocketAddr, SocketAddr) -> Version + Send + Sync + 'static,
+    {
+        self.version = Some(VersionFn(Arc::new(version)));
+        self
+    }
+
+    /// Sets the clock timestamping the node's messages, defaults to [`Clock::Real`].
+    ///
+    /// It only applies to the default handshake [`Version`], see [`SyntheticNode::clock`] for the
+    /// other messages.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the time allowed for the handshake to complete, defaults to 3 seconds.
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_options.timeout = timeout;
+        self
+    }
+
+    /// Fails the handshake with [`HandshakeError::VersionTooOld`] if the peer's protocol version is
+    /// lower than `version`.
+    pub fn with_min_peer_version(mut self, version: u32) -> Self {
+        self.handshake_options.min_version = Some(ProtocolVersion::new(version));
+        self
+    }
+
+    /// Delays writing the handshake's [`Verack`] by the given duration.
+    ///
+    /// Only applies to [`Handshake::Full`].
+    ///
+    /// [`Verack`]: enum@crate::protocol::message::Message::Verack
+    pub fn with_verack_delay(mut self, delay: Duration) -> Self {
+        self.handshake_options.verack_delay = Some(delay);
+        self
+    }
+
+    /// Writes the handshake's [`Verack`] before its [`Version`].
+    ///
+    /// Only applies to [`Handshake::Full`].
+    ///
+    /// [`Version`]: enum@crate::protocol::message::Message::Version
+    /// [`Verack`]: enum@crate::protocol::message::Message::Verack
+    pub fn with_verack_before_version(mut self) -> Self {
+        self.handshake_options.verack_first = true;
+        self
+    }
+
+    /// Writes the `messages` in between the handshake's [`Version`] and [`Verack`].
+    ///
+    /// Only applies to [`Handshake::Full`].
+    ///
+    /// [`Version`]: enum@crate::protocol::message::Message::Version
+    /// [`Verack`]: enum@crate::protocol::message::Message::Verack
+    pub fn with_messages_between_version_and_verack(mut self, messages: Vec<Message>) -> Self {
+        self.handshake_options.interleaved = messages;
+        self
+    }
+
     /// Sets the node's [`MessageFilter`].
     pub fn with_message_filter(mut self, filter: MessageFilter) -> Self {
         self.message_filter = filter;
         self
     }
 
+    /// Serves the [`Chain`] to peers, replying to their chain queries (see [`Chain`]) whatever
+    /// the [`MessageFilter`] is set to.
+    ///
+    /// Nodes built from this builder share the same chain.
+    pub fn with_chain(mut self, chain: Chain) -> Self {
+        self.chain = Some(Arc::new(chain));
+        self
+    }
+
+    /// Sets how many non-matching messages the node's expectations skip before failing, e.g.
+    /// [`SyntheticNode::expect`]. Defaults to `0`, i.e. any other message is unexpected.
+    pub fn with_skip_limit(mut self, limit: usize) -> Self {
+        self.skip_limit = limit;
+        self
+    }
+
+    /// Delivers frames which can't be decoded to the inbound queue as [`Inbound::Raw`], instead
+    /// of dropping the connection.
+    pub fn with_raw_frames(mut self) -> Self {
+        self.raw_frames = true;
+        self
+    }
+
+    /// Copies the bytes of every inbound frame (decodable or not) to
+    /// [`SyntheticNode::tapped_bytes`].
+    pub fn with_byte_tap(mut self) -> Self {
+        self.byte_tap = true;
+        self
+    }
+
+    /// Sets the [`TrafficShaping`] of the node's connections, unless overridden at runtime with
+    /// [`SyntheticNode::set_traffic_shaping`].
+    pub fn with_traffic_shaping(mut self, shaping: TrafficShaping) -> Self {
+        self.traffic_shaping = shaping;
+        self
+    }
+
+    /// Pushes the node's [`ConnectionStats`] to the metrics recorder as they're recorded, see
+    /// [`connection_stats::METRIC_MESSAGES`] and [`connection_stats::METRIC_BYTES`].
+    ///
+    /// The counters are registered on their first message, so clearing the recorder mid-connection
+    /// stops their updates.
+    pub fn with_stats_metrics(mut self) -> Self {
+        self.stats_metrics = true;
+        self
+    }
+
+    /// Sets the capacity of the node's inbound queue, defaults to `100`.
+    pub fn with_inbound_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "inbound capacity must be non-zero");
+
+        self.inbound_capacity = capacity;
+        self
+    }
+
+    /// Sets what happens to inbound messages when the node's inbound queue is full, defaults to
+    /// [`QueuePolicy::Block`].
+    pub fn with_inbound_policy(mut self, policy: QueuePolicy) -> Self {
+        self.inbound_policy = policy;
+        self
+    }
+
+    /// Enables a keepalive on each connection, once established (and handshaken, if enabled).
+    ///
+    /// A [`Ping`](Message::Ping) is sent every `interval`, its [`Pong`](Message::Pong) is
+    /// consumed in the background to record the round-trip time (see [`SyntheticNode::rtt`]), or
+    /// reported as [`Event::PongMissing`] if it doesn't arrive within `timeout`. The peer's pings
+    /// are also answered in the background, whatever the [`MessageFilter`] is set to.
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some(Keepalive { interval, timeout });
+        self
+    }
+
     /// Sets the node's write buffer size.
     pub fn with_max_write_buffer_size(mut self, size: usize) -> Self {
         let mut config = self.network_config.unwrap_or_default();
@@ -199,7 +809,12 @@ impl SyntheticNodeBuilder {
 /// Convenient abstraction over a `pea2pea` node.
 pub struct SyntheticNode {
     inner_node: InnerNode,
-    inbound_rx: Receiver<(SocketAddr, Message)>,
+    events_rx: UnboundedReceiver<(Instant, Event)>,
+    tap_rx: UnboundedReceiver<(SocketAddr, Vec<u8>)>,
+    chain: Option<Arc<Chain>>,
+    /// Messages taken off the inbound queue while reading from a specific peer, in order.
+    pending: VecDeque<(SocketAddr, Inbound)>,
+    skip_limit: usize,
 }
 
 impl SyntheticNode {
@@ -208,6 +823,19 @@ impl SyntheticNode {
         SyntheticNodeBuilder::default()
     }
 
+    /// Returns the [`Chain`] served by the node, if any.
+    pub fn chain(&self) -> Option<&Chain> {
+        self.chain.as_deref()
+    }
+
+    /// Returns the clock timestamping the node's messages, e.g. to construct them with
+    /// [`NetworkAddr::new_with_clock`].
+    ///
+    /// [`NetworkAddr::new_with_clock`]: crate::protocol::payload::addr::NetworkAddr::new_with_clock
+    pub fn clock(&self) -> Clock {
+        self.inner_node.clock
+    }
+
     /// Returns the listening address of the node.
     pub fn listening_addr(&self) -> SocketAddr {
         self.inner_node.node().listening_addr()
@@ -215,11 +843,17 @@ impl SyntheticNode {
 
     /// Connects to the target address.
     ///
-    /// If the handshake protocol is enabled it will be executed as well.
-    pub async fn connect(&self, target: SocketAddr) -> io::Result<()> {
-        self.inner_node.node().connect(target).await?;
+    /// If the handshake protocol is enabled it will be executed as well, its failure is returned
+    /// as the corresponding [`HandshakeError`].
+    pub async fn connect(&self, target: SocketAddr) -> Result<(), HandshakeError> {
+        self.inner_node.connect(target).await.map_err(|err| {
+            HandshakeError::from_connect_err(err, self.inner_node.handshake_options.timeout)
+        })
+    }
 
-        Ok(())
+    /// Returns the node's [`Event`] stream, along with the time of each event.
+    pub fn events(&mut self) -> &mut UnboundedReceiver<(Instant, Event)> {
+        &mut self.events_rx
     }
 
     /// Indicates if the `addr` is registered as a connected peer.
@@ -270,25 +904,32 @@ impl SyntheticNode {
         Ok(())
     }
 
-    /// Reads a message from the inbound (internal) queue of the node.
+    /// Returns the bytes of the inbound frames, if enabled with
+    /// [`SyntheticNodeBuilder::with_byte_tap`].
+    pub fn tapped_bytes(&mut self) -> &mut UnboundedReceiver<(SocketAddr, Vec<u8>)> {
+        &mut self.tap_rx
+    }
+
+    /// Reads an item from the inbound (internal) queue of the node.
     ///
-    /// Messages are sent to the queue when unfiltered by the message filter.
-    pub async fn recv_message(&mut self) -> (SocketAddr, Message) {
-        match self.inbound_rx.recv().await {
-            Some(message) => message,
-            None => panic!("all senders dropped!"),
+    /// Messages are sent to the queue when unfiltered by the message filter, undecodable frames
+    /// if enabled with [`SyntheticNodeBuilder::with_raw_frames`].
+    pub async fn recv_inbound(&mut self) -> (SocketAddr, Inbound) {
+        if let Some(inbound) = self.pending.pop_front() {
+            return inbound;
         }
+
+        self.inner_node.inbound.pop().await
     }
 
-    // Attempts to read a message from the inbound (internal) queue of the node before the timeout
-    // duration has elapsed (seconds).
-    // FIXME: logging?
-    pub async fn recv_message_timeout(
+    /// Attempts to read an item from the inbound (internal) queue of the node before the timeout
+    /// duration has elapsed.
+    pub async fn recv_inbound_timeout(
         &mut self,
         duration: Duration,
-    ) -> io::Result<(SocketAddr, Message)> {
-        match timeout(duration, self.recv_message()).await {
-            Ok(message) => Ok(message),
+    ) -> io::Result<(SocketAddr, Inbound)> {
+        match timeout(duration, self.recv_inbound()).await {
+            Ok(inbound) => Ok(inbound),
             Err(_e) => Err(Error::new(
                 ErrorKind::TimedOut,
                 format!(
@@ -299,30 +940,213 @@ impl SyntheticNode {
         }
     }
 
-    /// Sends [`Ping`], and expects [`Pong`] with a matching [`Nonce`] in reply.
+    /// Reads a message from the inbound (internal) queue of the node.
     ///
-    /// Uses polling to check that connection is still alive. Returns a [`PingPongError`] if:
-    /// - a non-[`Pong`] message is received
-    /// - a [`Pong`] with a non-matching [`Nonce`] is receives
-    /// - the timeout expires
-    /// - the connection breaks
-    /// - an [`io::Error`] occurs
+    /// Messages are sent to the queue when unfiltered by the message filter.
     ///
-    /// Is useful for checking a node's response to a prior query.
-    /// - if it was ignored, this call will succeed with `Ok(())`
-    /// - if there was a reply, it will be contained in [`Unexpected`](PingPongError::Unexpected)
-    /// - and [`ConnectionAborted`](PingPongError::ConnectionAborted) if the connection was terminated -
+    /// Panics on undecodable frames, [`recv_inbound`](Self::recv_inbound) should be used instead
+    /// if they are enabled.
+    pub async fn recv_message(&mut self) -> (SocketAddr, Message) {
+        match self.recv_inbound().await {
+            (source, Inbound::Message(message)) => (source, *message),
+            (source, raw) => panic!("received an undecodable frame from {}: {:?}", source, raw),
+        }
+    }
+
+    /// Reads the next item from `addr` off the inbound queue of the node, items from other peers
+    /// stay queued (in order) for later reads.
+    async fn recv_inbound_from_queue(&mut self, addr: SocketAddr) -> Inbound {
+        if let Some(i) = self.pending.iter().position(|(source, _)| *source == addr) {
+            return self.pending.remove(i).unwrap().1;
+        }
+
+        loop {
+            match self.inner_node.inbound.pop().await {
+                (source, inbound) if source == addr => return inbound,
+                inbound => self.pending.push_back(inbound),
+            }
+        }
+    }
+
+    /// Attempts to read the next message from `addr` before the timeout duration has elapsed.
     ///
-    /// [`Ping`]: enum@crate::protocol::message::Message::Ping
-    /// [`Pong`]: enum@crate::protocol::message::Message::Pong
-    /// [`Nonce`]: struct@crate::protocol::payload::Nonce
-    pub async fn ping_pong_timeout(
+    /// Messages from other peers are kept for subsequent reads.
+    pub async fn recv_message_from(
         &mut self,
-        target: SocketAddr,
+        addr: SocketAddr,
         duration: Duration,
-    ) -> Result<(), PingPongError> {
-        const SLEEP: Duration = Duration::from_millis(10);
-
+    ) -> io::Result<Message> {
+        match timeout(duration, self.recv_inbound_from_queue(addr)).await {
+            Ok(Inbound::Message(message)) => Ok(*message),
+            Ok(raw) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("received an undecodable frame from {}: {:?}", addr, raw),
+            )),
+            Err(_e) => Err(Error::new(
+                ErrorKind::TimedOut,
+                format!(
+                    "could not read message from {} after {1:.3}s",
+                    addr,
+                    duration.as_secs_f64()
+                ),
+            )),
+        }
+    }
+
+    /// Expects a message from `addr` for which `matcher` returns `true` before the timeout
+    /// expires.
+    ///
+    /// Up to the skip limit (see [`SyntheticNodeBuilder::with_skip_limit`]) of non-matching
+    /// messages are skipped, these are returned along with the match. Uses polling to check that
+    /// the connection is still alive.
+    pub async fn expect<F>(
+        &mut self,
+        addr: SocketAddr,
+        matcher: F,
+        duration: Duration,
+    ) -> Result<Expected, ExpectError>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        let deadline = Instant::now() + duration;
+        self.expect_before(addr, &matcher, deadline, duration).await
+    }
+
+    /// Expects a sequence of messages from `addr`, each one matching the corresponding `matcher`,
+    /// before the timeout expires.
+    ///
+    /// The skip limit applies to each of the expectations separately.
+    pub async fn expect_sequence(
+        &mut self,
+        addr: SocketAddr,
+        matchers: &[&dyn Fn(&Message) -> bool],
+        duration: Duration,
+    ) -> Result<Vec<Expected>, ExpectError> {
+        let deadline = Instant::now() + duration;
+
+        let mut expected = Vec::with_capacity(matchers.len());
+        for matcher in matchers {
+            expected.push(
+                self.expect_before(addr, matcher, deadline, duration)
+                    .await?,
+            );
+        }
+
+        Ok(expected)
+    }
+
+    /// Expects no messages from `addr` for the duration.
+    pub async fn expect_none(
+        &mut self,
+        addr: SocketAddr,
+        duration: Duration,
+    ) -> Result<(), ExpectError> {
+        match timeout(duration, self.recv_inbound_from_queue(addr)).await {
+            Ok(Inbound::Message(message)) => Err(ExpectError::Unexpected {
+                unexpected: message,
+                skipped: Vec::new(),
+            }),
+            Ok(Inbound::Raw { header, error, .. }) => Err(ExpectError::Undecodable {
+                header,
+                error,
+                skipped: Vec::new(),
+            }),
+            Err(_timeout) => Ok(()),
+        }
+    }
+
+    async fn expect_before(
+        &mut self,
+        addr: SocketAddr,
+        matcher: &dyn Fn(&Message) -> bool,
+        deadline: Instant,
+        duration: Duration,
+    ) -> Result<Expected, ExpectError> {
+        const SLEEP: Duration = Duration::from_millis(10);
+
+        let mut skipped = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::ZERO {
+                return Err(ExpectError::Timeout { duration, skipped });
+            }
+
+            match timeout(remaining.min(SLEEP), self.recv_inbound_from_queue(addr)).await {
+                Ok(Inbound::Message(message)) if matcher(&message) => {
+                    return Ok(Expected {
+                        message: *message,
+                        skipped,
+                    })
+                }
+                Ok(Inbound::Message(message)) if skipped.len() < self.skip_limit => {
+                    skipped.push(*message)
+                }
+                Ok(Inbound::Message(message)) => {
+                    return Err(ExpectError::Unexpected {
+                        unexpected: message,
+                        skipped,
+                    })
+                }
+                Ok(Inbound::Raw { header, error, .. }) => {
+                    return Err(ExpectError::Undecodable {
+                        header,
+                        error,
+                        skipped,
+                    })
+                }
+                Err(_timeout) => {
+                    // Check that connection is still alive, so that we can exit sooner
+                    if !self.is_connected(addr) {
+                        return Err(ExpectError::ConnectionAborted { skipped });
+                    }
+                }
+            }
+        }
+    }
+
+    // Attempts to read a message from the inbound (internal) queue of the node before the timeout
+    // duration has elapsed (seconds).
+    // FIXME: logging?
+    pub async fn recv_message_timeout(
+        &mut self,
+        duration: Duration,
+    ) -> io::Result<(SocketAddr, Message)> {
+        match timeout(duration, self.recv_message()).await {
+            Ok(message) => Ok(message),
+            Err(_e) => Err(Error::new(
+                ErrorKind::TimedOut,
+                format!(
+                    "could not read message after {0:.3}s",
+                    duration.as_secs_f64()
+                ),
+            )),
+        }
+    }
+
+    /// Sends [`Ping`], and expects [`Pong`] with a matching [`Nonce`] in reply.
+    ///
+    /// Uses polling to check that connection is still alive. Returns a [`PingPongError`] if:
+    /// - a non-[`Pong`] message is received
+    /// - a [`Pong`] with a non-matching [`Nonce`] is receives
+    /// - the timeout expires
+    /// - the connection breaks
+    /// - an [`io::Error`] occurs
+    ///
+    /// Is useful for checking a node's response to a prior query.
+    /// - if it was ignored, this call will succeed with `Ok(())`
+    /// - if there was a reply, it will be contained in [`Unexpected`](PingPongError::Unexpected)
+    /// - and [`ConnectionAborted`](PingPongError::ConnectionAborted) if the connection was terminated -
+    ///
+    /// [`Ping`]: enum@crate::protocol::message::Message::Ping
+    /// [`Pong`]: enum@crate::protocol::message::Message::Pong
+    /// [`Nonce`]: struct@crate::protocol::payload::Nonce
+    pub async fn ping_pong_timeout(
+        &mut self,
+        target: SocketAddr,
+        duration: Duration,
+    ) -> Result<(), PingPongError> {
+        const SLEEP: Duration = Duration::from_millis(10);
+
         let now = std::time::Instant::now();
         let ping_nonce = Nonce::default();
         if let Err(err) = self.send_direct_message(target, Message::Ping(ping_nonce)) {
@@ -363,14 +1187,98 @@ impl SyntheticNode {
         duration: Duration,
     ) -> io::Result<()> {
         match self.ping_pong_timeout(target, duration).await {
-            Ok(_) => Err(Error::new(ErrorKind::Other, "connection still active")),
+            Ok(_) => Err(Error::other("connection still active")),
             Err(PingPongError::ConnectionAborted) => Ok(()),
             Err(err) => Err(err.into()),
         }
     }
 
+    /// Returns the [`TrafficShaping`] of the connection to `addr`.
+    pub fn traffic_shaping(&self, addr: SocketAddr) -> TrafficShaping {
+        self.inner_node.traffic_shaping(addr)
+    }
+
+    /// Sets the [`TrafficShaping`] of the connection to `addr`, it applies from the next message
+    /// written.
+    pub fn set_traffic_shaping(&self, addr: SocketAddr, shaping: TrafficShaping) {
+        self.inner_node
+            .traffic_shaping
+            .write()
+            .connections
+            .insert(addr, shaping);
+    }
+
+    /// Pauses writing to `addr`, messages are queued up until writing is resumed.
+    pub fn pause_writing(&self, addr: SocketAddr) {
+        self.set_traffic_shaping(addr, self.traffic_shaping(addr).with_paused(true));
+    }
+
+    /// Resumes writing to `addr`.
+    pub fn resume_writing(&self, addr: SocketAddr) {
+        self.set_traffic_shaping(addr, self.traffic_shaping(addr).with_paused(false));
+    }
+
+    /// Pauses reading from `addr` while staying connected, messages which were already read but
+    /// not yet processed are held back as well.
+    ///
+    /// The bytes sent by the peer are left in the socket's receive buffer, so once it fills up
+    /// the peer can no longer write to the connection.
+    pub fn pause_reading(&self, addr: SocketAddr) {
+        self.inner_node.paused_reads.write().insert(addr);
+    }
+
+    /// Resumes reading from `addr`.
+    pub fn resume_reading(&self, addr: SocketAddr) {
+        self.inner_node.paused_reads.write().remove(&addr);
+    }
+
+    /// Returns the traffic statistics of the connection to `addr`, they are kept after
+    /// disconnecting.
+    pub fn stats(&self, addr: SocketAddr) -> ConnectionStats {
+        self.inner_node
+            .stats
+            .read()
+            .get(&addr)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Disconnects from `addr`, returns `false` if it wasn't connected.
+    pub fn disconnect(&self, addr: SocketAddr) -> bool {
+        self.inner_node
+            .disconnected(addr, DisconnectReason::LocalShutdown);
+        self.inner_node.node().disconnect(addr)
+    }
+
+    /// Returns the histogram of the keepalive round-trip times (in microseconds) of the connection
+    /// to `addr`, if any were recorded. The histogram is dropped once the connection is terminated.
+    pub fn rtt(&self, addr: SocketAddr) -> Option<Histogram> {
+        self.inner_node
+            .keepalives
+            .read()
+            .get(&addr)
+            .map(|state| state.rtt.clone())
+    }
+
+    /// Returns the number of messages waiting in the inbound queue.
+    pub fn inbound_len(&self) -> usize {
+        self.inner_node.inbound.len()
+    }
+
+    /// Returns the number of inbound messages dropped by the [`QueuePolicy`].
+    pub fn dropped_inbound(&self) -> u64 {
+        self.inner_node.inbound.dropped()
+    }
+
     /// Gracefully shuts down the node.
     pub fn shut_down(&self) {
+        for addr in self.connected_peers() {
+            self.inner_node
+                .disconnected(addr, DisconnectReason::LocalShutdown);
+        }
+
+        // Messages still being processed are dropped quietly.
+        self.inner_node.inbound.close();
         self.inner_node.node().shut_down()
     }
 }
@@ -385,29 +1293,287 @@ impl Drop for SyntheticNode {
 struct InnerNode {
     node: Node,
     handshake: Option<Handshake>,
-    inbound_tx: Sender<(SocketAddr, Message)>,
+    handshake_options: HandshakeOptions,
+    /// Constructs the handshake's [`Version`], if not the default one.
+    version: Option<VersionFn>,
+    clock: Clock,
+    inbound: Arc<InboundQueue<(SocketAddr, Inbound)>>,
+    events_tx: UnboundedSender<(Instant, Event)>,
+    /// Receives a copy of every inbound frame, if the byte tap is enabled.
+    tap_tx: Option<UnboundedSender<(SocketAddr, Vec<u8>)>>,
     message_filter: MessageFilter,
+    /// Deliver undecodable frames as [`Inbound::Raw`] instead of failing the read.
+    raw_frames: bool,
+    traffic_shaping: Arc<RwLock<TrafficShapingState>>,
+    /// The connections which aren't being read from.
+    paused_reads: Arc<RwLock<HashSet<SocketAddr>>>,
+    stats: Arc<RwLock<HashMap<SocketAddr, ConnectionStats>>>,
+    /// Push the stats to the metrics recorder.
+    stats_metrics: bool,
+    /// The connections which haven't been reported as terminated yet.
+    connections: Arc<RwLock<HashSet<SocketAddr>>>,
+    keepalive: Option<Keepalive>,
+    keepalives: Arc<RwLock<HashMap<SocketAddr, KeepaliveState>>>,
+}
+
+/// The keepalive of a [`SyntheticNode`], see [`SyntheticNodeBuilder::with_keepalive`].
+#[derive(Debug, Clone, Copy)]
+struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+}
+
+/// The keepalive state of a connection.
+struct KeepaliveState {
+    /// The nonces of the pings awaiting a pong, and when they were sent.
+    pending: Vec<(Nonce, Instant)>,
+    /// Round-trip times in microseconds.
+    rtt: Histogram,
+}
+
+impl Default for KeepaliveState {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            rtt: Histogram::new(),
+        }
+    }
+}
+
+/// The [`TrafficShaping`] of a [`SyntheticNode`]'s connections.
+#[derive(Debug)]
+struct TrafficShapingState {
+    /// Applies to connections without their own shaping.
+    default: TrafficShaping,
+    connections: HashMap<SocketAddr, TrafficShaping>,
 }
 
 impl InnerNode {
-    fn new(
-        node: Node,
-        tx: Sender<(SocketAddr, Message)>,
-        message_filter: MessageFilter,
-        handshake: Option<Handshake>,
-    ) -> Self {
-        let node = Self {
-            node,
-            inbound_tx: tx,
-            message_filter,
-            handshake,
+    fn traffic_shaping(&self, addr: SocketAddr) -> TrafficShaping {
+        let state = self.traffic_shaping.read();
+        state
+            .connections
+            .get(&addr)
+            .unwrap_or(&state.default)
+            .clone()
+    }
+
+    /// Sends the event to the node's event stream.
+    fn emit(&self, event: Event) {
+        // Events are optional, so a dropped receiver is ignored.
+        let _ = self.events_tx.send((Instant::now(), event));
+    }
+
+    /// Reports the connection as terminated, unless it already was.
+    fn disconnected(&self, addr: SocketAddr, reason: DisconnectReason) {
+        let removed = self.connections.write().remove(&addr);
+        // Removed after the connection, so the keepalive can't recreate it in between.
+        self.keepalives.write().remove(&addr);
+        if removed {
+            self.emit(Event::Disconnected { addr, reason });
+        }
+    }
+
+    /// Reports the connection as terminated if the stream error is fatal to it, mirroring
+    /// pea2pea.
+    fn stream_failed(&self, addr: SocketAddr, error: &io::Error) {
+        if self.node().config().fatal_io_errors.contains(&error.kind()) {
+            self.disconnected(addr, error.kind().into());
+        }
+    }
+
+    /// Pings the peer at the keepalive interval for as long as it's connected.
+    fn spawn_keepalive(&self, addr: SocketAddr) {
+        let keepalive = match self.keepalive {
+            Some(keepalive) => keepalive,
+            None => return,
         };
 
-        if handshake.is_some() {
-            node.enable_handshaking();
+        let node = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(keepalive.interval).await;
+
+                let nonce = Nonce::default();
+                {
+                    // Checked under the lock, so the state of a terminated connection isn't
+                    // recreated.
+                    let mut keepalives = node.keepalives.write();
+                    if !node.connections.read().contains(&addr) {
+                        break;
+                    }
+                    keepalives
+                        .entry(addr)
+                        .or_default()
+                        .pending
+                        .push((nonce, Instant::now()));
+                }
+
+                if node
+                    .send_direct_message(addr, Message::Ping(nonce))
+                    .is_err()
+                {
+                    // The outbound queue is full, so this ping is skipped.
+                    node.take_pending_ping(addr, nonce);
+                    continue;
+                }
+
+                let node = node.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(keepalive.timeout).await;
+                    if node.take_pending_ping(addr, nonce).is_some()
+                        && node.connections.read().contains(&addr)
+                    {
+                        node.emit(Event::PongMissing { addr, nonce });
+                    }
+                });
+            }
+        });
+    }
+
+    /// Removes the keepalive ping from the pending ones, returns when it was sent (if it was
+    /// pending).
+    fn take_pending_ping(&self, addr: SocketAddr, nonce: Nonce) -> Option<Instant> {
+        let mut keepalives = self.keepalives.write();
+        let pending = &mut keepalives.get_mut(&addr)?.pending;
+        let i = pending.iter().position(|(pending, _)| *pending == nonce)?;
+
+        Some(pending.remove(i).1)
+    }
+
+    /// Handles the keepalive messages, returns `true` if the message was consumed.
+    fn handle_keepalive(&self, source: SocketAddr, message: &Message) -> io::Result<bool> {
+        if self.keepalive.is_none() {
+            return Ok(false);
         }
 
-        node
+        match message {
+            Message::Ping(nonce) => {
+                self.send_direct_message(source, Message::Pong(*nonce))?;
+                Ok(true)
+            }
+            Message::Pong(nonce) => match self.take_pending_ping(source, *nonce) {
+                Some(sent) => {
+                    let rtt = sent.elapsed().as_micros() as u64;
+                    if let Some(state) = self.keepalives.write().get_mut(&source) {
+                        // Only fails for round trips beyond the histogram's maximum (over 16 hours).
+                        let _ = state.rtt.increment(rtt);
+                    }
+                    Ok(true)
+                }
+                // Not a keepalive pong, e.g. from `SyntheticNode::ping_pong_timeout`.
+                None => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// Connects to the target from the node's listening IP (see [`ListenerIp`]), unless it's
+    /// unspecified or of a different family than the target's.
+    async fn connect(&self, target: SocketAddr) -> io::Result<()> {
+        let ip = self.node().listening_addr().ip();
+        if ip.is_unspecified() || ip.is_ipv4() != target.is_ipv4() {
+            return self.node().connect(target).await;
+        }
+
+        let socket = match ip {
+            IpAddr::V4(_) => TcpSocket::new_v4()?,
+            IpAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.bind(SocketAddr::new(ip, 0))?;
+
+        self.node().connect_using(target, socket).await
+    }
+
+    /// Records a message (header included) in the connection's stats.
+    fn record_stats(&self, addr: SocketAddr, direction: Direction, command: &[u8], bytes: usize) {
+        let command = connection_stats::command_str(command);
+        let is_new = self
+            .stats
+            .write()
+            .entry(addr)
+            .or_default()
+            .record(direction, &command, bytes);
+
+        if self.stats_metrics {
+            let local = self.node().listening_addr();
+            connection_stats::record_metrics(local, addr, direction, &command, bytes, is_new);
+        }
+    }
+
+    /// Waits until reading from `addr` isn't paused.
+    async fn wait_for_reading(&self, addr: SocketAddr) {
+        const SLEEP: Duration = Duration::from_millis(10);
+
+        while self.paused_reads.read().contains(&addr) {
+            tokio::time::sleep(SLEEP).await;
+        }
+    }
+
+    /// Reads the frames from the stream, mirrors pea2pea's default
+    /// [`Reading::read_from_stream`], but holds the read back while it's paused.
+    async fn read_frames<R: AsyncRead + Unpin + Send>(
+        &self,
+        addr: SocketAddr,
+        buffer: &mut [u8],
+        reader: &mut R,
+        carry: usize,
+        message_sender: &Sender<Inbound>,
+    ) -> io::Result<usize> {
+        self.wait_for_reading(addr).await;
+
+        let read = match reader.read(&mut buffer[carry..]).await {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => n,
+            Err(error) => {
+                error!(parent: self.node().span(), "can't read from {}: {}", addr, error);
+                return Err(error);
+            }
+        };
+
+        // Reading may have been paused while waiting on the read, hold back what was read.
+        self.wait_for_reading(addr).await;
+
+        let mut processed = 0;
+        let mut left = carry + read;
+
+        // Several messages could have been read at once.
+        loop {
+            match self.read_message(addr, &buffer[processed..processed + left]) {
+                Ok(Some((inbound, len))) => {
+                    processed += len;
+                    left -= len;
+
+                    self.node()
+                        .known_peers()
+                        .register_received_message(addr, len);
+                    self.node().stats().register_received_message(len);
+
+                    if message_sender.send(inbound).await.is_err() {
+                        error!(parent: self.node().span(), "the inbound message channel is closed");
+                        return Err(ErrorKind::BrokenPipe.into());
+                    }
+
+                    if left == 0 {
+                        return Ok(0);
+                    }
+                }
+                Ok(None) => {
+                    if left >= buffer.len() {
+                        error!(parent: self.node().span(), "a message from {} is too large", addr);
+                        return Err(ErrorKind::InvalidData.into());
+                    }
+
+                    // Carry the incomplete message over to the next read.
+                    buffer.copy_within(processed..processed + left, 0);
+                    return Ok(left);
+                }
+                Err(error) => {
+                    error!(parent: self.node().span(), "a message from {} is invalid: {:?}", addr, error);
+                    return Err(ErrorKind::InvalidData.into());
+                }
+            }
+        }
     }
 
     fn send_direct_message(&self, target: SocketAddr, message: Message) -> io::Result<()> {
@@ -419,13 +1585,195 @@ impl InnerNode {
         header.encode(&mut buffer)?;
         buffer.append(&mut payload);
 
-        self.node().send_direct_message(target, buffer.into())?;
+        self.queue(target, &buffer)
+    }
+
+    fn send_direct_bytes(&self, target: SocketAddr, data: Vec<u8>) -> io::Result<()> {
+        self.queue(target, &data)
+    }
+
+    /// Queues the bytes to be written to the target, prefixed with the time they're queued at
+    /// (which the [`TrafficShaping`] latency is applied from).
+    fn queue(&self, target: SocketAddr, bytes: &[u8]) -> io::Result<()> {
+        let queued_at = QUEUE_EPOCH.elapsed().as_nanos() as u64;
+
+        let mut message = Vec::with_capacity(QUEUED_AT_LEN + bytes.len());
+        message.extend_from_slice(&queued_at.to_le_bytes());
+        message.extend_from_slice(bytes);
+
+        self.node().send_direct_message(target, message.into())
+    }
+
+    /// Constructs the handshake's [`Version`] using the configured [`VersionFn`], or the node's
+    /// clock.
+    fn version(&self, addr_recv: SocketAddr, addr_from: SocketAddr) -> Message {
+        let version = match self.version {
+            Some(ref version) => (version.0)(addr_recv, addr_from),
+            None => Version::new_with_clock(addr_recv, addr_from, self.clock),
+        };
+
+        Message::Version(version)
+    }
+
+    /// Writes a handshake message to the connection.
+    async fn write_handshake_message(
+        &self,
+        conn: &mut Connection,
+        message: &Message,
+    ) -> Result<(), HandshakeError> {
+        message
+            .write_to_stream(conn.writer())
+            .await
+            .map_err(HandshakeError::from_stream_err)?;
+
+        // The message is encoded again for its header, which is cheap for handshake messages.
+        let mut body = Vec::new();
+        let header = message
+            .encode(&mut body)
+            .map_err(HandshakeError::from_stream_err)?;
+        self.record_stats(
+            conn.addr,
+            Direction::Outbound,
+            &header.command,
+            HEADER_LEN + body.len(),
+        );
 
         Ok(())
     }
 
-    fn send_direct_bytes(&self, target: SocketAddr, data: Vec<u8>) -> io::Result<()> {
-        self.node.send_direct_message(target, data.into())
+    /// Writes the handshake's [`Verack`](Message::Verack), after the configured delay.
+    async fn write_verack(&self, conn: &mut Connection) -> Result<(), HandshakeError> {
+        if let Some(delay) = self.handshake_options.verack_delay {
+            tokio::time::sleep(delay).await;
+        }
+
+        self.write_handshake_message(conn, &Message::Verack).await
+    }
+
+    /// Writes the messages configured to go in between the handshake's [`Version`](Message::Version)
+    /// and [`Verack`](Message::Verack).
+    async fn write_interleaved(&self, conn: &mut Connection) -> Result<(), HandshakeError> {
+        for message in &self.handshake_options.interleaved {
+            self.write_handshake_message(conn, message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a handshake message from the connection, distinguishing stream and decoding failures.
+    async fn read_handshake_message(
+        &self,
+        conn: &mut Connection,
+    ) -> Result<Message, HandshakeError> {
+        let header = MessageHeader::read_from_stream(conn.reader())
+            .await
+            .map_err(HandshakeError::from_stream_err)?;
+
+        if header.body_length as usize > MAX_MESSAGE_LEN {
+            return Err(HandshakeError::Decode(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Message length of {} exceeds max message length of {}",
+                    header.body_length, MAX_MESSAGE_LEN
+                ),
+            )));
+        }
+
+        let mut body = vec![0u8; header.body_length as usize];
+        conn.reader()
+            .read_exact(&mut body)
+            .await
+            .map_err(HandshakeError::from_stream_err)?;
+
+        self.record_stats(
+            conn.addr,
+            Direction::Inbound,
+            &header.command,
+            HEADER_LEN + body.len(),
+        );
+
+        Message::decode(header.command, &mut Cursor::new(&body[..])).map_err(HandshakeError::Decode)
+    }
+
+    /// Reads the peer's [`Version`], checking it against the configured minimum version.
+    async fn read_version(&self, conn: &mut Connection) -> Result<Version, HandshakeError> {
+        match self.read_handshake_message(conn).await? {
+            Message::Version(version) => match self.handshake_options.min_version {
+                Some(min_version) if version.version < min_version => {
+                    Err(HandshakeError::VersionTooOld(version.version))
+                }
+                _ => Ok(version),
+            },
+            other => Err(HandshakeError::UnexpectedMessage(other.into())),
+        }
+    }
+
+    /// Reads the peer's [`Verack`](Message::Verack).
+    async fn read_verack(&self, conn: &mut Connection) -> Result<(), HandshakeError> {
+        match self.read_handshake_message(conn).await? {
+            Message::Verack => Ok(()),
+            other => Err(HandshakeError::UnexpectedMessage(other.into())),
+        }
+    }
+
+    /// Performs the handshake with the configured [`Handshake`] and [`HandshakeOptions`].
+    async fn handshake(&self, conn: &mut Connection, full: bool) -> Result<(), HandshakeError> {
+        let verack_first = full && self.handshake_options.verack_first;
+
+        match !conn.side {
+            ConnectionSide::Initiator => {
+                // Possible bug: running zebra node results in internal pea2pea panics:
+                //  "thread 'tokio-runtime-worker' panicked at 'internal error: entered unreachable code'"
+                // which gets "fixed" by reversing the parameters in Version::new -- no current insight into
+                // why this is the case. The panic is triggered by the following code in pea2pea:
+                // https://docs.rs/pea2pea/0.20.3/src/pea2pea/node.rs.html#201
+
+                // Send and receive Version.
+                if verack_first {
+                    self.write_verack(conn).await?;
+                    self.write_interleaved(conn).await?;
+                }
+
+                let version = self.version(conn.addr, self.node().listening_addr());
+                self.write_handshake_message(conn, &version).await?;
+
+                self.read_version(conn).await?;
+
+                if full {
+                    // Send and receive Verack.
+                    if !verack_first {
+                        self.write_interleaved(conn).await?;
+                        self.write_verack(conn).await?;
+                    }
+
+                    self.read_verack(conn).await?;
+                }
+            }
+            ConnectionSide::Responder => {
+                // Receive and send Version.
+                let node_addr = self.read_version(conn).await?.addr_from.addr;
+
+                if verack_first {
+                    self.write_verack(conn).await?;
+                    self.write_interleaved(conn).await?;
+                }
+
+                let version = self.version(node_addr, self.node().listening_addr());
+                self.write_handshake_message(conn, &version).await?;
+
+                if full {
+                    // Receive and send Verack.
+                    self.read_verack(conn).await?;
+
+                    if !verack_first {
+                        self.write_interleaved(conn).await?;
+                        self.write_verack(conn).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -437,11 +1785,30 @@ impl Pea2Pea for InnerNode {
 
 #[async_trait::async_trait]
 impl Reading for InnerNode {
-    type Message = Message;
+    type Message = Inbound;
+
+    async fn read_from_stream<R: AsyncRead + Unpin + Send>(
+        &self,
+        addr: SocketAddr,
+        buffer: &mut [u8],
+        reader: &mut R,
+        carry: usize,
+        message_sender: &Sender<Self::Message>,
+    ) -> io::Result<usize> {
+        let result = self
+            .read_frames(addr, buffer, reader, carry, message_sender)
+            .await;
+
+        if let Err(ref error) = result {
+            self.stream_failed(addr, error);
+        }
+
+        result
+    }
 
     fn read_message(
         &self,
-        _source: SocketAddr,
+        source: SocketAddr,
         buffer: &[u8],
     ) -> io::Result<Option<(Self::Message, usize)>> {
         // Check buffer contains a full header.
@@ -454,29 +1821,63 @@ impl Reading for InnerNode {
         let header = MessageHeader::decode(&mut Cursor::new(header_bytes))?;
 
         // Check buffer contains the announced message length.
-        if buffer.len() < HEADER_LEN + header.body_length as usize {
-            return Err(ErrorKind::InvalidData.into());
+        let frame_len = HEADER_LEN + header.body_length as usize;
+        if buffer.len() < frame_len {
+            return Ok(None);
+        }
+
+        if let Some(tap_tx) = &self.tap_tx {
+            // The tap is optional, so a dropped receiver is ignored.
+            let _ = tap_tx.send((source, buffer[..frame_len].to_vec()));
         }
 
+        self.record_stats(source, Direction::Inbound, &header.command, frame_len);
+
         // Decode message.
-        let mut bytes = Cursor::new(&buffer[HEADER_LEN..][..header.body_length as usize]);
-        let message = Message::decode(header.command, &mut bytes)?;
+        let body = &buffer[HEADER_LEN..frame_len];
+        let inbound = match Message::decode(header.command, &mut Cursor::new(body)) {
+            Ok(message) => Inbound::Message(message.into()),
+            Err(error) if self.raw_frames => {
+                debug!(parent: self.node().span(), "undecodable frame from {}: {:?}", source, error);
+                Inbound::Raw {
+                    header,
+                    bytes: body.to_vec(),
+                    error,
+                }
+            }
+            Err(error) => return Err(error),
+        };
 
-        // Read the position from the cursor.
-        Ok(Some((message, HEADER_LEN + bytes.position() as usize)))
+        // The whole frame is consumed, even if the message was decoded from fewer bytes.
+        Ok(Some((inbound, frame_len)))
     }
 
-    async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
+    async fn process_message(&self, source: SocketAddr, inbound: Self::Message) -> io::Result<()> {
         let span = self.node().span().clone();
 
+        let message = match inbound {
+            Inbound::Message(message) => message,
+            raw => {
+                // Undecodable frames bypass the message filter.
+                self.inbound.push((source, raw)).await;
+
+                return Ok(());
+            }
+        };
+
+        if self.handle_keepalive(source, &message)? {
+            debug!(parent: span, "keepalive consumed {:?}", message);
+            return Ok(());
+        }
+
         debug!(parent: span.clone(), "processing {:?}", message);
         match self.message_filter.message_filter_type(&message) {
-            Filter::AutoReply => {
-                // Autoreply with the appropriate response.
-                let response = self.message_filter.reply_message(&message);
-
-                debug!(parent: span, "auto replying with {:?}", response);
-                self.send_direct_message(source, response)?;
+            Filter::AutoReply | Filter::Custom(_) => {
+                // Reply with the default or custom responses, in order.
+                for response in self.message_filter.reply_messages(source, &message) {
+                    debug!(parent: span.clone(), "auto replying with {:?}", response);
+                    self.send_direct_message(source, response)?;
+                }
             }
 
             Filter::Disabled => {
@@ -485,10 +1886,7 @@ impl Reading for InnerNode {
                     parent: span,
                     "sending the message to the node's inbound queue"
                 );
-                self.inbound_tx
-                    .send((source, message))
-                    .await
-                    .expect("receiver dropped!");
+                self.inbound.push((source, Inbound::Message(message))).await;
             }
 
             Filter::Enabled => {
@@ -501,7 +1899,47 @@ impl Reading for InnerNode {
     }
 }
 
+#[async_trait::async_trait]
 impl Writing for InnerNode {
+    async fn write_to_stream<W: AsyncWrite + Unpin + Send>(
+        &self,
+        message: &[u8],
+        addr: SocketAddr,
+        buffer: &mut [u8],
+        writer: &mut W,
+    ) -> io::Result<usize> {
+        const SLEEP: Duration = Duration::from_millis(10);
+
+        // Every message is prefixed with the time it was queued at, see `InnerNode::queue`.
+        let (queued_at, message) = message.split_at(QUEUED_AT_LEN);
+        let queued_at = *QUEUE_EPOCH
+            + Duration::from_nanos(u64::from_le_bytes(
+                queued_at.try_into().expect("the prefix has a fixed length"),
+            ));
+
+        let len = self.write_message(addr, message, buffer)?;
+
+        // The shaping can change at runtime, so it's checked again while paused.
+        let mut shaping = self.traffic_shaping(addr);
+        while shaping.is_paused() {
+            tokio::time::sleep(SLEEP).await;
+            shaping = self.traffic_shaping(addr);
+        }
+
+        if let Err(error) = shaping.write(&buffer[..len], queued_at, writer).await {
+            self.stream_failed(addr, &error);
+            return Err(error);
+        }
+
+        // Raw bytes may not start with a header, their command is then left empty.
+        let command = MessageHeader::decode(&mut Cursor::new(&buffer[..len]))
+            .map(|header| header.command)
+            .unwrap_or_default();
+        self.record_stats(addr, Direction::Outbound, &command, len);
+
+        Ok(len)
+    }
+
     fn write_message(
         &self,
         _target: SocketAddr,
@@ -516,83 +1954,997 @@ impl Writing for InnerNode {
 #[async_trait::async_trait]
 impl Handshaking for InnerNode {
     async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
-        match (self.handshake, !conn.side) {
-            (Some(Handshake::Full), ConnectionSide::Initiator) => {
-                // Possible bug: running zebra node results in internal pea2pea panics:
-                //  "thread 'tokio-runtime-worker' panicked at 'internal error: entered unreachable code'"
-                // which gets "fixed" by reversing the parameters in Version::new -- no current insight into
-                // why this is the case. The panic is triggered by the following code in pea2pea:
-                // https://docs.rs/pea2pea/0.20.3/src/pea2pea/node.rs.html#201
+        let addr = conn.addr;
+        self.connections.write().insert(addr);
+        self.emit(Event::Connected {
+            addr,
+            side: !conn.side,
+        });
+
+        let full = match self.handshake {
+            Some(handshake) => handshake == Handshake::Full,
+            None => {
+                self.spawn_keepalive(addr);
+                return Ok(conn);
+            }
+        };
 
-                // Send and receive Version.
-                Message::Version(Version::new(conn.addr, self.node().listening_addr()))
-                    .write_to_stream(conn.writer())
-                    .await?;
+        let handshake_timeout = self.handshake_options.timeout;
+        let result = match timeout(handshake_timeout, self.handshake(&mut conn, full)).await {
+            Ok(result) => result,
+            Err(_elapsed) => Err(HandshakeError::Timeout(handshake_timeout)),
+        };
+
+        match result {
+            Ok(()) => {
+                self.emit(Event::Handshaken { addr });
+                self.spawn_keepalive(addr);
+                Ok(conn)
+            }
+            Err(error) => {
+                let span = self.node().span().clone();
+                error!(parent: span, "handshake with {} failed: {:?}", addr, error);
+                self.connections.write().remove(&addr);
+
+                match !conn.side {
+                    // Also returned to the caller of `SyntheticNode::connect`.
+                    ConnectionSide::Initiator => {
+                        self.emit(Event::HandshakeFailed {
+                            addr,
+                            error: error.duplicate(),
+                        });
+                        Err(error.into())
+                    }
+                    ConnectionSide::Responder => {
+                        self.emit(Event::HandshakeFailed { addr, error });
+                        Err(ErrorKind::ConnectionAborted.into())
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::payload::Inv,
+        tools::{connection_stats::CommandStats, TIMEOUT},
+        wait_until,
+    };
+
+    use assert_matches::assert_matches;
+
+    #[tokio::test]
+    #[ignore]
+    async fn handshake_version_uses_the_clock() {
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        let offset = chrono::Duration::hours(-5);
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_clock(Clock::Offset(offset))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(synthetic_node.clock(), Clock::Offset(offset));
+        let _handshake = tokio::spawn(async move { synthetic_node.connect(peer_addr).await });
+
+        let (_, version) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        let version = assert_matches!(version, Message::Version(version) => version);
+        let skew = version.timestamp - chrono::Utc::now();
+        assert!((skew - offset).num_seconds().abs() <= 1);
+
+        // Messages timestamped by a fixed clock are encoded identically.
+        let clock = Clock::Fixed(version.timestamp);
+        let nonce = Nonce::default();
+        let encode = || {
+            let mut version = Version::new_with_clock(peer_addr, peer_addr, clock);
+            version.nonce = nonce;
+            let mut buffer = Vec::new();
+            Message::Version(version).encode(&mut buffer).unwrap();
+            buffer
+        };
+        assert_eq!(encode(), encode());
+    }
 
-                let version = Message::read_from_stream(conn.reader()).await?;
-                assert_matches!(version, Message::Version(..));
+    #[tokio::test]
+    #[ignore]
+    async fn handshake_uses_custom_version() {
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_version(|addr_recv, addr_from| {
+                Version::new(addr_recv, addr_from).with_version(170_002)
+            })
+            .build()
+            .await
+            .unwrap();
+        let handshake = tokio::spawn(async move {
+            synthetic_node.connect(peer_addr).await.unwrap();
+            synthetic_node
+        });
+
+        let (source, version) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        let version = assert_matches!(version, Message::Version(version) => version);
+        assert_eq!(
+            version.version,
+            Version::new(source, source).with_version(170_002).version
+        );
+        assert_eq!(version.addr_recv.addr, peer_addr);
+
+        peer.send_direct_message(source, Message::Version(Version::new(source, peer_addr)))
+            .unwrap();
+        let (_, verack) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_matches!(verack, Message::Verack);
+        peer.send_direct_message(source, Message::Verack).unwrap();
+
+        let synthetic_node = handshake.await.unwrap();
+        assert!(synthetic_node.is_connected(peer_addr));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn handshake_writes_verack_first_with_interleaved_messages() {
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        let nonce = Nonce::default();
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_verack_before_version()
+            .with_messages_between_version_and_verack(vec![Message::Ping(nonce)])
+            .build()
+            .await
+            .unwrap();
+        let handshake = tokio::spawn(async move {
+            synthetic_node.connect(peer_addr).await.unwrap();
+            synthetic_node
+        });
+
+        let (source, verack) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_matches!(verack, Message::Verack);
+        let (_, ping) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(ping, Message::Ping(nonce));
+        let (_, version) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_matches!(version, Message::Version(..));
+
+        peer.send_direct_message(source, Message::Version(Version::new(source, peer_addr)))
+            .unwrap();
+        peer.send_direct_message(source, Message::Verack).unwrap();
+
+        let synthetic_node = handshake.await.unwrap();
+        assert!(synthetic_node.is_connected(peer_addr));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn responder_handshake_delays_verack() {
+        const DELAY: Duration = Duration::from_millis(200);
+
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_verack_delay(DELAY)
+            .build()
+            .await
+            .unwrap();
+        let node_addr = synthetic_node.listening_addr();
+
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+        peer.connect(node_addr).await.unwrap();
+        peer.send_direct_message(
+            node_addr,
+            Message::Version(Version::new(node_addr, peer.listening_addr())),
+        )
+        .unwrap();
+
+        let (_, version) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_matches!(version, Message::Version(..));
+        peer.send_direct_message(node_addr, Message::Verack)
+            .unwrap();
 
-                // Send and receive Verack.
-                Message::Verack.write_to_stream(conn.writer()).await?;
+        let now = std::time::Instant::now();
+        let (_, verack) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_matches!(verack, Message::Verack);
+        assert!(now.elapsed() >= DELAY);
+
+        crate::wait_until!(TIMEOUT, synthetic_node.num_connected() == 1);
+    }
 
-                let verack = Message::read_from_stream(conn.reader()).await?;
-                assert_matches!(verack, Message::Verack);
+    #[tokio::test]
+    #[ignore]
+    async fn handshake_fails_on_unexpected_message() {
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .build()
+            .await
+            .unwrap();
+        let handshake = tokio::spawn(async move { synthetic_node.connect(peer_addr).await });
+
+        // Reply to the Version with a Verack.
+        let (source, _version) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        peer.send_direct_message(source, Message::Verack).unwrap();
+
+        assert_matches!(
+            handshake.await.unwrap(),
+            Err(HandshakeError::UnexpectedMessage(message)) if *message == Message::Verack
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn handshake_times_out() {
+        const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(100);
+
+        let peer = SyntheticNode::builder().build().await.unwrap();
+        let synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_handshake_timeout(HANDSHAKE_TIMEOUT)
+            .build()
+            .await
+            .unwrap();
+
+        assert_matches!(
+            synthetic_node.connect(peer.listening_addr()).await,
+            Err(HandshakeError::Timeout(HANDSHAKE_TIMEOUT))
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn responder_handshake_failure_is_an_event() {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_min_peer_version(170_002)
+            .build()
+            .await
+            .unwrap();
+        let node_addr = synthetic_node.listening_addr();
+
+        let peer = SyntheticNode::builder().build().await.unwrap();
+        peer.connect(node_addr).await.unwrap();
+        peer.send_direct_message(
+            node_addr,
+            Message::Version(Version::new(node_addr, peer.listening_addr()).with_version(170_001)),
+        )
+        .unwrap();
+
+        let (_, event) = timeout(TIMEOUT, synthetic_node.events().recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_matches!(
+            event,
+            Event::Connected {
+                side: ConnectionSide::Responder,
+                ..
             }
-            (Some(Handshake::Full), ConnectionSide::Responder) => {
-                // Receive and send Version.
-                let version = Message::read_from_stream(conn.reader()).await?;
-                let node_addr = match version {
-                    Message::Version(version) => version.addr_from.addr,
-                    other => {
-                        let span = self.node().span().clone();
-                        error!(
-                            parent: span,
-                            "received non-version message during handshake: {:?}", other
-                        );
-                        panic!("Expected Version, got {:?}", other);
-                    }
-                };
+        );
+
+        let (_, event) = timeout(TIMEOUT, synthetic_node.events().recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_matches!(
+            event,
+            Event::HandshakeFailed { error: HandshakeError::VersionTooOld(version), .. }
+                if version == ProtocolVersion::new(170_001)
+        );
+        assert_eq!(synthetic_node.num_connected(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn custom_filter_replies_with_every_message() {
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+
+        let responder = SyntheticNode::builder()
+            .with_message_filter(MessageFilter::with_all_disabled().with_mempool_filter(
+                Filter::custom(|_, _| vec![Message::Inv(Inv::empty()), Message::GetAddr]),
+            ))
+            .build()
+            .await
+            .unwrap();
+        let responder_addr = responder.listening_addr();
+
+        peer.connect(responder_addr).await.unwrap();
+        peer.send_direct_message(responder_addr, Message::MemPool)
+            .unwrap();
+
+        let (_, inv) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(inv, Message::Inv(Inv::empty()));
+        let (_, getaddr) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(getaddr, Message::GetAddr);
+    }
+
+    /// Connects a node to two peers, returning the node, the peers and the node's address as
+    /// seen by each peer.
+    async fn connect_to_two_peers(
+        builder: SyntheticNodeBuilder,
+    ) -> (SyntheticNode, Vec<SyntheticNode>, Vec<SocketAddr>) {
+        let synthetic_node = builder.build().await.unwrap();
+
+        let mut peers = Vec::new();
+        let mut addrs = Vec::new();
+        for _ in 0..2 {
+            let peer = SyntheticNode::builder().build().await.unwrap();
+            synthetic_node.connect(peer.listening_addr()).await.unwrap();
+            addrs.push(peer.wait_for_connection().await);
+            peers.push(peer);
+        }
+
+        (synthetic_node, peers, addrs)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn recv_message_from_keeps_other_peers_messages() {
+        let (mut synthetic_node, peers, addrs) =
+            connect_to_two_peers(SyntheticNode::builder()).await;
+        let (a, b) = (peers[0].listening_addr(), peers[1].listening_addr());
+
+        peers[0]
+            .send_direct_message(addrs[0], Message::GetAddr)
+            .unwrap();
+        wait_until!(TIMEOUT, synthetic_node.inbound_len() == 1);
+        peers[1]
+            .send_direct_message(addrs[1], Message::MemPool)
+            .unwrap();
+
+        let message = synthetic_node.recv_message_from(b, TIMEOUT).await.unwrap();
+        assert_eq!(message, Message::MemPool);
+
+        let (source, message) = synthetic_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!((source, message), (a, Message::GetAddr));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn expect_skips_up_to_the_limit() {
+        let (mut synthetic_node, peers, addrs) =
+            connect_to_two_peers(SyntheticNode::builder().with_skip_limit(2)).await;
+        let a = peers[0].listening_addr();
+
+        for message in [Message::GetAddr, Message::MemPool, Message::Verack] {
+            peers[0].send_direct_message(addrs[0], message).unwrap();
+        }
+        peers[1]
+            .send_direct_message(addrs[1], Message::GetAddr)
+            .unwrap();
+
+        let expected = synthetic_node
+            .expect(a, |message| matches!(message, Message::Verack), TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(expected.message, Message::Verack);
+        assert_eq!(expected.skipped, vec![Message::GetAddr, Message::MemPool]);
+
+        let ping = Message::Ping(Nonce::default());
+        for message in [Message::GetAddr, Message::MemPool, ping.clone()] {
+            peers[0].send_direct_message(addrs[0], message).unwrap();
+        }
+
+        let error = synthetic_node
+            .expect(a, |message| matches!(message, Message::Verack), TIMEOUT)
+            .await
+            .unwrap_err();
+        assert_matches!(
+            error,
+            ExpectError::Unexpected { unexpected, skipped }
+                if *unexpected == ping
+                    && skipped == vec![Message::GetAddr, Message::MemPool]
+        );
+    }
 
-                Message::Version(Version::new(node_addr, self.node().listening_addr()))
-                    .write_to_stream(conn.writer())
-                    .await?;
+    #[tokio::test]
+    #[ignore]
+    async fn expect_sequence_and_none() {
+        let (mut synthetic_node, peers, addrs) =
+            connect_to_two_peers(SyntheticNode::builder()).await;
+        let (a, b) = (peers[0].listening_addr(), peers[1].listening_addr());
+
+        peers[0]
+            .send_direct_message(addrs[0], Message::GetAddr)
+            .unwrap();
+        peers[0]
+            .send_direct_message(addrs[0], Message::Verack)
+            .unwrap();
+
+        let expected = synthetic_node
+            .expect_sequence(
+                a,
+                &[
+                    &|message: &Message| matches!(message, Message::GetAddr),
+                    &|message: &Message| matches!(message, Message::Verack),
+                ],
+                TIMEOUT,
+            )
+            .await
+            .unwrap();
+        assert_eq!(expected.len(), 2);
+        assert!(expected.iter().all(|expected| expected.skipped.is_empty()));
+
+        synthetic_node
+            .expect_none(b, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        peers[1]
+            .send_direct_message(addrs[1], Message::MemPool)
+            .unwrap();
+        assert_matches!(
+            synthetic_node.expect_none(b, TIMEOUT).await,
+            Err(ExpectError::Unexpected { unexpected, .. }) if *unexpected == Message::MemPool
+        );
+    }
 
-                // Receive and send Verack.
-                let verack = Message::read_from_stream(conn.reader()).await?;
-                assert_matches!(verack, Message::Verack);
+    #[tokio::test]
+    #[ignore]
+    async fn expect_fails_when_connection_is_aborted() {
+        let (mut synthetic_node, peers, _) = connect_to_two_peers(SyntheticNode::builder()).await;
+        let a = peers[0].listening_addr();
+
+        peers[0].shut_down();
+
+        assert_matches!(
+            synthetic_node
+                .expect(a, |message| matches!(message, Message::Verack), TIMEOUT)
+                .await,
+            Err(ExpectError::ConnectionAborted { .. })
+        );
+    }
 
-                Message::Verack.write_to_stream(conn.writer()).await?;
+    /// Returns a frame with a valid header for a [`Ping`](Message::Ping) and a truncated nonce.
+    fn undecodable_ping() -> (MessageHeader, Vec<u8>) {
+        use crate::protocol::message::constants::PING_COMMAND;
+
+        let body = vec![1, 2, 3];
+        let header = MessageHeader::new(PING_COMMAND, &body);
+
+        let mut frame = Vec::new();
+        header.encode(&mut frame).unwrap();
+        frame.extend_from_slice(&body);
+
+        (header, frame)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn undecodable_frames_are_delivered_raw() {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_raw_frames()
+            .build()
+            .await
+            .unwrap();
+        let peer = SyntheticNode::builder().build().await.unwrap();
+
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+        let addr = peer.wait_for_connection().await;
+
+        let (header, frame) = undecodable_ping();
+        peer.send_direct_bytes(addr, frame).unwrap();
+        peer.send_direct_message(addr, Message::Verack).unwrap();
+
+        let (_, raw) = synthetic_node.recv_inbound_timeout(TIMEOUT).await.unwrap();
+        let (raw_header, bytes, error) = assert_matches!(
+            raw,
+            Inbound::Raw { header, bytes, error } => (header, bytes, error)
+        );
+        assert_eq!(raw_header.command, header.command);
+        assert_eq!(bytes, vec![1, 2, 3]);
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+        // The connection is kept alive and the following message is decoded as usual.
+        let (_, message) = synthetic_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(message, Message::Verack);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn byte_tap_copies_every_frame() {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_byte_tap()
+            .build()
+            .await
+            .unwrap();
+        let peer = SyntheticNode::builder().build().await.unwrap();
+
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+        let addr = peer.wait_for_connection().await;
+
+        let mut verack = Vec::new();
+        Message::Verack
+            .encode(&mut Vec::new())
+            .unwrap()
+            .encode(&mut verack)
+            .unwrap();
+        let (_, undecodable) = undecodable_ping();
+
+        peer.send_direct_message(addr, Message::Verack).unwrap();
+        peer.send_direct_bytes(addr, undecodable.clone()).unwrap();
+
+        let tap = synthetic_node.tapped_bytes();
+        let (_, bytes) = timeout(TIMEOUT, tap.recv()).await.unwrap().unwrap();
+        assert_eq!(bytes, verack);
+        let (_, bytes) = timeout(TIMEOUT, tap.recv()).await.unwrap().unwrap();
+        assert_eq!(bytes, undecodable);
+
+        // Without raw frames, the undecodable frame still drops the connection.
+        wait_until!(TIMEOUT, !synthetic_node.is_connected(peer.listening_addr()));
+    }
+
+    /// Connects a node with the given builder to a peer, returning both.
+    async fn connect_to_peer(builder: SyntheticNodeBuilder) -> (SyntheticNode, SyntheticNode) {
+        let synthetic_node = builder.build().await.unwrap();
+        let peer = SyntheticNode::builder().build().await.unwrap();
+
+        synthetic_node.connect(peer.listening_addr()).await.unwrap();
+        peer.wait_for_connection().await;
+
+        (synthetic_node, peer)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn chunked_messages_are_reassembled() {
+        // A Ping frame is 32 bytes, written in 7 chunks.
+        const DELAY: Duration = Duration::from_millis(20);
+        let shaping = TrafficShaping::new().with_chunks(5, DELAY);
+        let (synthetic_node, mut peer) =
+            connect_to_peer(SyntheticNode::builder().with_traffic_shaping(shaping)).await;
+
+        let start = Instant::now();
+        let nonce = Nonce::default();
+        synthetic_node
+            .send_direct_message(peer.listening_addr(), Message::Ping(nonce))
+            .unwrap();
+
+        let (_, message) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(message, Message::Ping(nonce));
+        assert!(start.elapsed() >= 6 * DELAY);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn latency_delays_messages() {
+        const LATENCY: Duration = Duration::from_millis(100);
+        let (synthetic_node, mut peer) = connect_to_peer(SyntheticNode::builder()).await;
+        let peer_addr = peer.listening_addr();
+
+        let shaping = TrafficShaping::new().with_latency(LATENCY, Duration::from_millis(50));
+        synthetic_node.set_traffic_shaping(peer_addr, shaping.clone());
+        assert_eq!(synthetic_node.traffic_shaping(peer_addr), shaping);
+
+        let start = Instant::now();
+        synthetic_node
+            .send_direct_message(peer_addr, Message::Verack)
+            .unwrap();
+
+        let (_, message) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(message, Message::Verack);
+        assert!(start.elapsed() >= LATENCY);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn latency_doesnt_add_up_over_a_burst() {
+        const LATENCY: Duration = Duration::from_millis(100);
+        const BURST: usize = 10;
+        let shaping = TrafficShaping::new().with_latency(LATENCY, Duration::ZERO);
+        let (synthetic_node, mut peer) =
+            connect_to_peer(SyntheticNode::builder().with_traffic_shaping(shaping)).await;
+
+        let start = Instant::now();
+        for _ in 0..BURST {
+            synthetic_node
+                .send_direct_message(peer.listening_addr(), Message::Verack)
+                .unwrap();
+        }
+        for _ in 0..BURST {
+            let (_, message) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+            assert_eq!(message, Message::Verack);
+        }
+
+        assert!(start.elapsed() >= LATENCY && start.elapsed() < 2 * LATENCY);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn paused_writes_are_resumed_in_order() {
+        let (synthetic_node, mut peer) = connect_to_peer(SyntheticNode::builder()).await;
+        let peer_addr = peer.listening_addr();
+        let addr = peer.connected_peers()[0];
+
+        synthetic_node.pause_writing(peer_addr);
+        synthetic_node
+            .send_direct_message(peer_addr, Message::GetAddr)
+            .unwrap();
+        synthetic_node
+            .send_direct_message(peer_addr, Message::Verack)
+            .unwrap();
+
+        peer.expect_none(addr, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        synthetic_node.resume_writing(peer_addr);
+        peer.expect_sequence(
+            addr,
+            &[
+                &|message: &Message| matches!(message, Message::GetAddr),
+                &|message: &Message| matches!(message, Message::Verack),
+            ],
+            TIMEOUT,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn paused_reads_are_resumed_in_order() {
+        let (synthetic_node, mut peer) = connect_to_peer(SyntheticNode::builder()).await;
+        let peer_addr = peer.listening_addr();
+        let addr = peer.connected_peers()[0];
+
+        peer.pause_reading(addr);
+        synthetic_node
+            .send_direct_message(peer_addr, Message::GetAddr)
+            .unwrap();
+        synthetic_node
+            .send_direct_message(peer_addr, Message::Verack)
+            .unwrap();
+
+        peer.expect_none(addr, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert!(peer.is_connected(addr));
+
+        peer.resume_reading(addr);
+        peer.expect_sequence(
+            addr,
+            &[
+                &|message: &Message| matches!(message, Message::GetAddr),
+                &|message: &Message| matches!(message, Message::Verack),
+            ],
+            TIMEOUT,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn stats_count_handshake_and_messages() {
+        let builder = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply();
+        let mut synthetic_node = builder.build().await.unwrap();
+        let peer = builder.build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        synthetic_node.connect(peer_addr).await.unwrap();
+        synthetic_node
+            .ping_pong_timeout(peer_addr, TIMEOUT)
+            .await
+            .unwrap();
+
+        let stats = synthetic_node.stats(peer_addr);
+        for command in &["version", "verack"] {
+            assert_eq!(stats.outbound.command(command).messages, 1);
+            assert_eq!(stats.inbound.command(command).messages, 1);
+        }
+        assert_eq!(
+            stats.outbound.command("ping"),
+            CommandStats {
+                messages: 1,
+                bytes: (HEADER_LEN + 8) as u64
             }
-            (Some(Handshake::VersionOnly), ConnectionSide::Initiator) => {
-                Message::Version(Version::new(conn.addr, self.node().listening_addr()))
-                    .write_to_stream(conn.writer())
-                    .await?;
+        );
+        assert_eq!(stats.inbound.command("pong").messages, 1);
+        assert_eq!(stats.outbound.bytes(), stats.inbound.bytes());
+
+        // The peer's view of the connection mirrors ours.
+        let peer_stats = peer.stats(peer.connected_peers()[0]);
+        assert_eq!(peer_stats.inbound.bytes(), stats.outbound.bytes());
+        assert_eq!(peer_stats.outbound.messages(), stats.inbound.messages());
+    }
 
-                let version = Message::read_from_stream(conn.reader()).await?;
-                assert_matches!(version, Message::Version(..));
+    #[tokio::test]
+    #[ignore]
+    async fn stats_metrics_of_nodes_sharing_a_peer_add_up() {
+        use crate::tools::metrics::recorder;
+
+        let _recorder = recorder::enable_exclusive().await;
+
+        let builder = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply()
+            .with_stats_metrics();
+        let peer = builder.build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        let mut synthetic_nodes = Vec::new();
+        for _ in 0..2 {
+            let mut synthetic_node = builder.build().await.unwrap();
+            synthetic_node.connect(peer_addr).await.unwrap();
+            for _ in 0..3 {
+                synthetic_node
+                    .ping_pong_timeout(peer_addr, TIMEOUT)
+                    .await
+                    .unwrap();
             }
-            (Some(Handshake::VersionOnly), ConnectionSide::Responder) => {
-                // Receive and send Version.
-                let version = Message::read_from_stream(conn.reader()).await?;
-                let node_addr = match version {
-                    Message::Version(version) => version.addr_from.addr,
-                    other => {
-                        let span = self.node().span().clone();
-                        error!(
-                            parent: span,
-                            "received non-version message during handshake: {:?}", other
-                        );
-                        panic!("Expected Version, got {:?}", other);
-                    }
+            synthetic_nodes.push(synthetic_node);
+        }
+
+        // The counters are filtered by peer, as other tests may share the recorder.
+        let sent_pings: u64 = recorder::counters()
+            .lock()
+            .iter()
+            .filter(|(key, _)| {
+                let label = |name| {
+                    key.labels()
+                        .find(|label| label.key() == name)
+                        .map(|label| label.value().to_owned())
                 };
+                key.name() == connection_stats::METRIC_MESSAGES
+                    && label("peer") == Some(peer_addr.to_string())
+                    && label("command").as_deref() == Some("ping")
+                    && label("direction").as_deref() == Some("out")
+            })
+            .map(|(_, counter)| counter.value)
+            .sum();
+        assert_eq!(sent_pings, 6);
+    }
+
+    async fn next_event(node: &mut SyntheticNode) -> (Instant, Event) {
+        timeout(TIMEOUT, node.events().recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
 
-                Message::Version(Version::new(node_addr, self.node().listening_addr()))
-                    .write_to_stream(conn.writer())
-                    .await?;
+    #[tokio::test]
+    #[ignore]
+    async fn lifecycle_events_are_emitted_in_order() {
+        let builder = SyntheticNode::builder().with_full_handshake();
+        let mut synthetic_node = builder.build().await.unwrap();
+        let mut peer = builder.build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        synthetic_node.connect(peer_addr).await.unwrap();
+        let addr = peer.wait_for_connection().await;
+
+        let (connected_at, event) = next_event(&mut synthetic_node).await;
+        assert_matches!(event, Event::Connected { addr, side: ConnectionSide::Initiator } if addr == peer_addr);
+        let (handshaken_at, event) = next_event(&mut synthetic_node).await;
+        assert_matches!(event, Event::Handshaken { addr } if addr == peer_addr);
+        assert!(connected_at <= handshaken_at);
+
+        assert_matches!(
+            next_event(&mut peer).await.1,
+            Event::Connected {
+                side: ConnectionSide::Responder,
+                ..
             }
-            (None, _) => {}
+        );
+        assert_matches!(next_event(&mut peer).await.1, Event::Handshaken { .. });
+
+        assert!(synthetic_node.disconnect(peer_addr));
+        assert_matches!(
+            next_event(&mut synthetic_node).await.1,
+            Event::Disconnected {
+                reason: DisconnectReason::LocalShutdown,
+                ..
+            }
+        );
+        assert_matches!(
+            next_event(&mut peer).await.1,
+            Event::Disconnected { addr: disconnected, reason: DisconnectReason::Eof | DisconnectReason::Reset }
+                if disconnected == addr
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn undecodable_frame_disconnect_is_an_event() {
+        let (synthetic_node, mut peer) = connect_to_peer(SyntheticNode::builder()).await;
+        let addr = peer.connected_peers()[0];
+
+        let (_, frame) = undecodable_ping();
+        synthetic_node
+            .send_direct_bytes(peer.listening_addr(), frame)
+            .unwrap();
+
+        assert_matches!(next_event(&mut peer).await.1, Event::Connected { .. });
+        assert_matches!(
+            next_event(&mut peer).await.1,
+            Event::Disconnected { addr: disconnected, reason: DisconnectReason::Decode }
+                if disconnected == addr
+        );
+        assert!(!peer.is_connected(addr));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn full_inbound_queue_applies_policy() {
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_inbound_capacity(2)
+            .with_inbound_policy(QueuePolicy::DropOldest)
+            .build()
+            .await
+            .unwrap();
+        let peer = SyntheticNode::builder().build().await.unwrap();
+        let node_addr = synthetic_node.listening_addr();
+
+        peer.connect(node_addr).await.unwrap();
+        let addr = synthetic_node.wait_for_connection().await;
+
+        let nonces = (0..5).map(|_| Nonce::default()).collect::<Vec<_>>();
+        for nonce in &nonces {
+            peer.send_direct_message(node_addr, Message::Ping(*nonce))
+                .unwrap();
+        }
+        wait_until!(TIMEOUT, synthetic_node.dropped_inbound() == 3);
+
+        // Only the newest messages are kept.
+        for nonce in &nonces[3..] {
+            let message = synthetic_node
+                .recv_message_from(addr, TIMEOUT)
+                .await
+                .unwrap();
+            assert_eq!(message, Message::Ping(*nonce));
         }
+        assert_eq!(synthetic_node.inbound_len(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn spread_nodes_listen_on_distinct_ips() {
+        let (nodes, addrs) = SyntheticNode::builder().build_n_distinct(3).await.unwrap();
+
+        let ips = addrs.iter().map(|addr| addr.ip()).collect::<HashSet<_>>();
+        assert_eq!(ips.len(), 3);
+        assert!(ips.iter().all(|ip| ip.is_loopback() && ip.is_ipv4()));
+        assert!(!ips.contains(&IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+        // The nodes are reachable on their IPs.
+        nodes[0].connect(addrs[1]).await.unwrap();
+        nodes[2].connect(addrs[1]).await.unwrap();
+        wait_until!(TIMEOUT, nodes[1].num_connected() == 2);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn outbound_connections_come_from_the_listener_ip() {
+        let builder = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_all_auto_reply();
+        let (mut nodes, addrs) = builder.build_n_distinct(1).await.unwrap();
+        let mut peer = builder.build().await.unwrap();
+
+        nodes[0].connect(peer.listening_addr()).await.unwrap();
+        nodes[0]
+            .ping_pong_timeout(peer.listening_addr(), TIMEOUT)
+            .await
+            .unwrap();
+
+        // The peer only ever sees the connection from the node's IP.
+        assert_matches!(
+            next_event(&mut peer).await.1,
+            Event::Connected { addr, .. } if addr.ip() == addrs[0].ip()
+        );
+        assert_matches!(next_event(&mut peer).await.1, Event::Handshaken { .. });
+        assert!(peer.events().try_recv().is_err());
+        assert_matches!(&peer.connected_peers()[..], [addr] if addr.ip() == addrs[0].ip());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn dual_stack_nodes_alternate_with_ipv6() {
+        let builder = SyntheticNode::builder().with_listener_ip(ListenerIp::SpreadDualStack);
+        let (nodes, addrs) = builder.build_n(2).await.unwrap();
+
+        assert_eq!(addrs.iter().filter(|addr| addr.is_ipv6()).count(), 1);
+        let (v4, v6) = if addrs[0].is_ipv6() { (1, 0) } else { (0, 1) };
+        assert_eq!(addrs[v6].ip(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+
+        nodes[v4].connect(addrs[v6]).await.unwrap();
+        nodes[v6].wait_for_connection().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn keepalive_records_rtt_in_the_background() {
+        const INTERVAL: Duration = Duration::from_millis(20);
+        let builder = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_keepalive(INTERVAL, TIMEOUT);
+        let mut synthetic_node = builder.build().await.unwrap();
+        let peer = builder.build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+        synthetic_node.connect(peer_addr).await.unwrap();
+        let addr = peer.wait_for_connection().await;
+
+        wait_until!(
+            TIMEOUT,
+            synthetic_node
+                .rtt(peer_addr)
+                .is_some_and(|rtt| rtt.entries() >= 3)
+                && peer.rtt(addr).is_some_and(|rtt| rtt.entries() >= 3)
+        );
+
+        // Neither the pings nor the pongs reach the inbound queues.
+        assert_eq!(synthetic_node.inbound_len(), 0);
+        assert_eq!(peer.inbound_len(), 0);
+        synthetic_node
+            .expect_none(peer_addr, INTERVAL * 2)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn keepalive_state_is_removed_on_disconnect() {
+        let builder = SyntheticNode::builder()
+            .with_full_handshake()
+            .with_keepalive(Duration::from_millis(20), TIMEOUT);
+        let synthetic_node = builder.build().await.unwrap();
+        let peer = builder.build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+        synthetic_node.connect(peer_addr).await.unwrap();
+        let addr = peer.wait_for_connection().await;
+
+        wait_until!(
+            TIMEOUT,
+            synthetic_node.rtt(peer_addr).is_some() && peer.rtt(addr).is_some()
+        );
+
+        // Both the local and the remote end of the connection drop its state.
+        assert!(synthetic_node.disconnect(peer_addr));
+        assert!(synthetic_node.rtt(peer_addr).is_none());
+        wait_until!(TIMEOUT, peer.rtt(addr).is_none());
+        assert!(peer.inner_node.keepalives.read().is_empty());
+        assert!(synthetic_node.inner_node.keepalives.read().is_empty());
+    }
 
-        Ok(conn)
+    #[tokio::test]
+    #[ignore]
+    async fn missing_pong_is_an_event() {
+        const TIMEOUT_PONG: Duration = Duration::from_millis(50);
+        let mut synthetic_node = SyntheticNode::builder()
+            .with_keepalive(Duration::from_millis(20), TIMEOUT_PONG)
+            .build()
+            .await
+            .unwrap();
+        // The peer doesn't answer the pings, they're queued up instead.
+        let mut peer = SyntheticNode::builder().build().await.unwrap();
+        let peer_addr = peer.listening_addr();
+
+        synthetic_node.connect(peer_addr).await.unwrap();
+        let addr = peer.wait_for_connection().await;
+
+        assert_matches!(
+            next_event(&mut synthetic_node).await.1,
+            Event::Connected { .. }
+        );
+        let (_, event) = next_event(&mut synthetic_node).await;
+        let (_, ping) = peer.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert_matches!(
+            (event, ping),
+            (Event::PongMissing { addr: missing, nonce }, Message::Ping(ping_nonce))
+                if missing == peer_addr && nonce == ping_nonce
+        );
+        assert!(synthetic_node
+            .rtt(peer_addr)
+            .is_none_or(|rtt| rtt.entries() == 0));
+        assert!(peer.is_connected(addr));
     }
 }
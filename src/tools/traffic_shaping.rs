@@ -0,0 +1,155 @@
+//! Traffic shaping for the connections of a synthetic node.
+
+use rand::{thread_rng, Rng};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    time::{sleep, sleep_until},
+};
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+/// Shapes the traffic written to a connection, e.g. to simulate a slow or distant peer.
+///
+/// The shaping applies to each message as it's written, after the handshake:
+/// 1. writing is held back for as long as it's paused,
+/// 2. the message is held back until the latency plus a random amount of jitter have elapsed
+///    since it was queued,
+/// 3. the message is written in chunks (or whole), with a delay in between chunks,
+/// 4. after each chunk, writing is held back long enough to respect the bandwidth.
+///
+/// The latency is counted from the time each message is queued, so a burst of messages arrives
+/// about one latency later. As messages are written one at a time and in order, the other delays
+/// also hold back the messages queued up after them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrafficShaping {
+    latency: Duration,
+    jitter: Duration,
+    /// Bandwidth in bytes per second.
+    bandwidth: Option<u64>,
+    /// Chunk size and the delay in between chunks.
+    chunks: Option<(usize, Duration)>,
+    paused: bool,
+}
+
+impl TrafficShaping {
+    /// Creates a `TrafficShaping` which doesn't alter the traffic.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Delays each message by `latency` plus a random duration of up to `jitter`, counted from
+    /// the time it's queued.
+    pub fn with_latency(mut self, latency: Duration, jitter: Duration) -> Self {
+        self.latency = latency;
+        self.jitter = jitter;
+        self
+    }
+
+    /// Caps the bandwidth to `bytes_per_sec`.
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "bandwidth must be non-zero");
+
+        self.bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    /// Splits each message into chunks of `size` bytes, which are written `delay` apart.
+    pub fn with_chunks(mut self, size: usize, delay: Duration) -> Self {
+        assert!(size > 0, "chunk size must be non-zero");
+
+        self.chunks = Some((size, delay));
+        self
+    }
+
+    /// Sets whether writing is paused.
+    pub fn with_paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    /// Returns `true` if writing is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Writes the bytes queued at `queued_at` to the writer according to the shaping, doesn't
+    /// take pausing into account.
+    pub(super) async fn write<W: AsyncWrite + Unpin>(
+        &self,
+        bytes: &[u8],
+        queued_at: Instant,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let delay = self.latency + self.jitter.mul_f64(thread_rng().gen_range(0.0..=1.0));
+        if !delay.is_zero() {
+            // A no-op if the time already passed, e.g. while writing the previous messages.
+            sleep_until((queued_at + delay).into()).await;
+        }
+
+        let (chunk_size, chunk_delay) = self.chunks.unwrap_or((bytes.len().max(1), Duration::ZERO));
+        for (i, chunk) in bytes.chunks(chunk_size).enumerate() {
+            if i > 0 && !chunk_delay.is_zero() {
+                sleep(chunk_delay).await;
+            }
+
+            writer.write_all(chunk).await?;
+            writer.flush().await?;
+
+            if let Some(bandwidth) = self.bandwidth {
+                sleep(Duration::from_secs_f64(
+                    chunk.len() as f64 / bandwidth as f64,
+                ))
+                .await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    #[tokio::test]
+    #[ignore]
+    async fn bandwidth_and_chunks_preserve_bytes() {
+        let bytes = (0..100).collect::<Vec<u8>>();
+        let shaping = TrafficShaping::new()
+            .with_bandwidth(1000)
+            .with_chunks(30, Duration::from_millis(10));
+
+        let start = Instant::now();
+        let mut written = Vec::new();
+        shaping
+            .write(&bytes, Instant::now(), &mut written)
+            .await
+            .unwrap();
+
+        // 100 bytes at 1000 B/s, plus 3 delays in between 4 chunks.
+        assert!(start.elapsed() >= Duration::from_millis(130));
+        assert_eq!(written, bytes);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn latency_is_counted_from_queueing() {
+        const LATENCY: Duration = Duration::from_millis(100);
+        let shaping = TrafficShaping::new().with_latency(LATENCY, Duration::ZERO);
+
+        // A burst of messages queued at once arrives after a single latency.
+        let start = Instant::now();
+        let mut written = Vec::new();
+        for i in 0..10 {
+            shaping.write(&[i], start, &mut written).await.unwrap();
+        }
+
+        assert!(start.elapsed() >= LATENCY && start.elapsed() < 2 * LATENCY);
+        assert_eq!(written, (0..10).collect::<Vec<u8>>());
+    }
+}